@@ -0,0 +1,32 @@
+use super::*;
+
+/// Where the files that define a book's structure are.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BookPaths {
+    pub book_toml: PathBuf,
+    pub src_dir: PathBuf,
+    pub summary_md: PathBuf,
+}
+
+impl BookPaths {
+    /// Paths of the book at `book_root` configured with `config`.
+    pub fn new(book_root: &Path, config: &Config) -> Self {
+        let src_dir = book_root.join(&config.book.src);
+        Self {
+            book_toml: book_root.join("book.toml"),
+            summary_md: src_dir.join("SUMMARY.md"),
+            src_dir,
+        }
+    }
+
+    /// Paths of the book at `book_root` according to its `book.toml`,
+    /// or the default ones if there is no `book.toml`.
+    pub fn load(book_root: &Path) -> Result<Self> {
+        let book_toml = book_root.join("book.toml");
+        let config = match book_toml.exists() {
+            true => Config::from_disk(&book_toml)?,
+            false => Config::default(),
+        };
+        Ok(Self::new(book_root, &config))
+    }
+}
@@ -5,19 +5,20 @@ use std::{
     ffi::OsStr,
     io, iter, mem,
     net::SocketAddr,
+    ops::Range,
     path::{Path, PathBuf},
     sync::{Arc, Mutex, RwLock},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
 use anyhow::{bail, Context};
 use drop_this::*;
-use futures_util::sink::SinkExt;
+use futures_util::{sink::SinkExt, stream::StreamExt};
 use handlebars::Handlebars;
 use ignore::gitignore::Gitignore;
 use mdbook::{
-    book::{preprocessor_should_run, Book, Chapter},
-    config::{Code, HtmlConfig, Playground, RustEdition},
+    book::{load_book, parse_summary, preprocessor_should_run, Book, Chapter, Link, SummaryItem},
+    config::{Code, HtmlConfig, Playground, RustEdition, Search},
     errors::*,
     preprocess::{Preprocessor, PreprocessorContext},
     renderer::{
@@ -32,13 +33,17 @@ use mdbook::{
 };
 use notify::{RecommendedWatcher, RecursiveMode::*};
 use notify_debouncer_mini::{DebounceEventHandler, DebouncedEvent, Debouncer};
+use once_cell::sync::Lazy;
+use pulldown_cmark::{Event, Tag};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tempfile::{tempdir, TempDir};
 use tokio::{
     fs::{self, File},
     io::AsyncReadExt,
     select, spawn,
-    sync::{mpsc, oneshot, watch},
+    sync::{broadcast, mpsc, oneshot, watch},
     task::{block_in_place, spawn_blocking, yield_now, JoinHandle},
     time::timeout,
 };
@@ -56,21 +61,33 @@ use warp::{
     Filter,
 };
 
+pub mod book_paths;
 pub mod build_book;
 pub mod git_ignore;
+pub mod live_patch_protocol;
 pub mod patch_registry;
 pub mod previewing;
+pub mod print_page;
 pub mod rebuilding;
 pub mod rendering;
+pub mod search_index;
+pub mod source_lines;
+pub mod summary_patching;
 pub mod watch_files;
 pub mod web_server;
 
+use book_paths::*;
 use build_book::*;
 use git_ignore::*;
+use live_patch_protocol::*;
 use patch_registry::*;
 use previewing::*;
+use print_page::*;
 use rebuilding::*;
 use rendering::*;
+use search_index::*;
+use source_lines::*;
+use summary_patching::*;
 use watch_files::*;
 use web_server::*;
 
@@ -87,7 +104,7 @@ pub async fn preview_continuously(
     socket_address: SocketAddr,
     open_browser: bool,
 ) -> Result<()> {
-    let previewer = Previewer::try_new()?;
+    let previewer = Previewer::try_new(None)?;
     let (handle, actor_ref) = previewer.spawn();
     actor_ref.cast(PreviewInfo::BookRoot(book_root)).await?;
     let msg = PreviewInfo::OpenPreview {
@@ -0,0 +1,74 @@
+use super::*;
+
+/// Version of the WebSocket protocol between
+/// [`handle_ws`] and `websocket_live_patch.js`;
+/// bump it whenever the messages change.
+pub const PROTOCOL_VERSION: u32 = 4;
+
+/// How long to wait for the browser tab's [`LivePatchRequest::Hello`].
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The reload message of the pages from before [`PROTOCOL_VERSION`],
+/// which send no handshake;
+/// `websocket_live_patch.js` also reloads on it if its hello is late.
+pub const LEGACY_RELOAD: &str = "__RELOAD";
+
+/// A message from the server to the browser tab, serialized as JSON.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LivePatchMessage {
+    /// The handshake succeeded, with the ID of the latest build.
+    Welcome { build: u64 },
+    /// Patch `id` with new HTML `<main>` body content,
+    /// and the source lines of the changed blocks, if known.
+    Patch {
+        id: u64,
+        html: String,
+        changed_lines: Vec<usize>,
+    },
+    /// Reload the page.
+    ///
+    /// NOTE: Keep this message stable across protocol versions because
+    /// it is how we tell outdated pages to reload.
+    Reload,
+    /// Show the error `message` over the stale content until the next patch.
+    Error { message: String },
+    /// Scroll to the block at zero-based `line` of the source.
+    ScrollTo { line: usize },
+    /// The search index in `searchindex.json` was re-created.
+    SearchIndex,
+    /// The table of contents in `toc.js` was re-rendered.
+    Toc,
+}
+
+impl LivePatchMessage {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Serializing a live patch message")
+    }
+}
+
+/// A message from the browser tab to the server, deserialized from JSON.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LivePatchRequest {
+    /// The first message, with the tab's [`PROTOCOL_VERSION`], and
+    /// the IDs of the build and the patch it last applied when reconnecting.
+    Hello {
+        version: u32,
+        #[serde(default)]
+        build: Option<u64>,
+        #[serde(default)]
+        patch: Option<u64>,
+    },
+    /// Show zero-based `line` of the chapter's source in the editor.
+    ShowSource { line: usize },
+}
+
+impl LivePatchRequest {
+    pub fn from_message(message: &Message) -> Option<Self> {
+        let text = message.to_str().ok()?;
+        serde_json::from_str(text)
+            .map_err(|err| debug!(?err, text, "Parsing the live patch request."))
+            .ok()
+    }
+}
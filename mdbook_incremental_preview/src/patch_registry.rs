@@ -1,16 +1,79 @@
-use std::collections::hash_map::Entry;
-
 use super::*;
 
 /// A registry of watch channel senders of patches for paths.
-#[derive(Default)]
 pub struct PatchRegistry {
-    /// Preprocessed markdown content and watch channel for
-    /// HTML `<main>` body content of each patched path.
-    patches: HashMap<PathBuf, (String, watch::Sender<String>)>,
+    /// The patch of each patched path.
+    patches: HashMap<PathBuf, Patch>,
     /// Relative HTTP path of the index chapter.
     index_path: Option<PathBuf>,
     process_cfg: ProcessCfg,
+    /// Broadcast of messages to the tabs at a relative HTTP path,
+    /// or to all tabs if [`None`].
+    broadcast_tx: broadcast::Sender<(Option<PathBuf>, LivePatchMessage)>,
+    /// Watch channel of what to re-create the search index from.
+    search_index_tx: watch::Sender<Option<SearchIndexSource>>,
+    /// Task re-creating the search index on changes.
+    reindex_search: Option<JoinHandle<()>>,
+    /// Watch channel of what to re-render `print.html` from,
+    /// if printing is enabled.
+    print_page_tx: watch::Sender<Option<PrintPage>>,
+    /// Task re-rendering `print.html` on changes.
+    rewrite_print_page: Option<JoinHandle<()>>,
+    /// Patched paths whose pages were rendered anew for
+    /// the table of contents since their last patch,
+    /// so reloading them needs no full rebuild.
+    rerendered: HashSet<PathBuf>,
+    /// If to scroll to the first changed block after each patch.
+    scroll_to_changes: bool,
+    /// ID of the latest full build, unique across restarts.
+    build_id: u64,
+    /// ID of the next patch.
+    next_patch_id: u64,
+}
+
+/// Preprocessed markdown content, the rendered HTML `<main>` body content,
+/// the source line of each Markdown line,
+/// and watch channel for the latest message, if any, of a patched path.
+#[derive(Default)]
+struct Patch {
+    markdown: String,
+    html: String,
+    source_lines: Vec<usize>,
+    sender: watch::Sender<Option<LivePatchMessage>>,
+}
+
+impl PatchRegistry {
+    pub fn new(scroll_to_changes: bool) -> Self {
+        Self {
+            patches: Default::default(),
+            index_path: None,
+            process_cfg: Default::default(),
+            broadcast_tx: broadcast::channel(BROADCAST_CHANNEL_CAPACITY).0,
+            search_index_tx: Default::default(),
+            reindex_search: None,
+            print_page_tx: Default::default(),
+            rewrite_print_page: None,
+            rerendered: HashSet::new(),
+            scroll_to_changes,
+            build_id: 0,
+            next_patch_id: 0,
+        }
+    }
+}
+
+/// Only the latest scroll or search index matters,
+/// so lagging receivers may skip the rest.
+const BROADCAST_CHANNEL_CAPACITY: usize = 4;
+
+impl Drop for PatchRegistry {
+    fn drop(&mut self) {
+        if let Some(reindex_search) = &self.reindex_search {
+            reindex_search.abort();
+        }
+        if let Some(rewrite_print_page) = &self.rewrite_print_page {
+            rewrite_print_page.abort();
+        }
+    }
 }
 
 impl Actor for PatchRegistry {
@@ -18,45 +81,186 @@ impl Actor for PatchRegistry {
     type T = PatchRegistryRequest;
     type R = PatchRegistryResponse;
 
+    async fn init(&mut self, env: &mut ActorRef<Self>) -> Result<()> {
+        self.reindex_search = Some(spawn(reindex_search_continuously(
+            self.search_index_tx.subscribe(),
+            self.broadcast_tx.clone(),
+        )));
+        self.rewrite_print_page = Some(spawn(rewrite_print_page_continuously(
+            self.print_page_tx.subscribe(),
+            env.clone(),
+        )));
+        Ok(())
+    }
+
     async fn handle_cast(&mut self, msg: Self::T, _env: &mut ActorRef<Self>) -> Result<()> {
         match msg {
-            PatchRegistryRequest::NewPatch(path, new_markdown) => {
+            PatchRegistryRequest::NewPatch(path, new_markdown, source_lines) => {
                 debug!(?path, "Registry received patch.");
-                match self.patches.entry(path) {
-                    // Entry exists,
-                    // update the patch in-place and send watch updates.
-                    Entry::Occupied(mut entry) => {
-                        let (markdown, sender) = entry.get_mut();
-                        // Update the patch only if it changed.
-                        if *markdown != new_markdown {
-                            debug!("Updating patch in registry.");
-                            let rendered =
-                                block_n_yield(|| self.process_cfg.render_markdown(&new_markdown))
-                                    .await;
-                            let new_html =
-                                block_n_yield(|| self.process_cfg.post_process(rendered)).await;
-                            sender.send_modify(|html| *html = new_html);
-                        }
-                    }
-                    // New entry, register the patch and a new watch channel.
-                    Entry::Vacant(entry) => {
-                        _ = entry.insert((Default::default(), watch::channel(new_markdown).0))
-                    }
+                self.rerendered.remove(&path);
+                let patch = self.patches.entry(path.clone()).or_default();
+                // Update the patch only if it changed.
+                if patch.markdown == new_markdown && patch.source_lines == source_lines {
+                    return Ok(());
+                }
+                debug!("Updating patch in registry.");
+                let marked = block_n_yield(|| {
+                    self.process_cfg
+                        .mark_source_lines(&new_markdown, &source_lines)
+                })
+                .await;
+                let rendered = block_n_yield(|| self.process_cfg.render_markdown(&marked)).await;
+                let html = block_n_yield(|| self.process_cfg.post_process(rendered)).await;
+                let new_html = block_n_yield(|| annotate_source_lines(&html)).await;
+                // NOTE: We do not know the fully rendered page the tabs show
+                // before the first patch, so we only mark changes after it.
+                let changed_lines = match patch.html.is_empty() {
+                    true => Vec::new(),
+                    false => block_n_yield(|| changed_source_lines(&patch.html, &new_html)).await,
+                };
+                let message = LivePatchMessage::Patch {
+                    id: self.next_patch_id,
+                    html: new_html.clone(),
+                    changed_lines: changed_lines.clone(),
                 };
+                self.next_patch_id += 1;
+                patch.sender.send_modify(|v| *v = Some(message));
+                self.search_index_tx.send_if_modified(|search_index| {
+                    search_index
+                        .as_mut()
+                        .is_some_and(|source| source.update_chapter(&path, &new_markdown))
+                });
+                (patch.markdown, patch.html, patch.source_lines) =
+                    (new_markdown, new_html, source_lines);
+                if let (true, Some(&line)) = (self.scroll_to_changes, changed_lines.first()) {
+                    let scroll = LivePatchMessage::ScrollTo { line };
+                    // No receivers is fine.
+                    _ = self.broadcast_tx.send((Some(path.clone()), scroll));
+                }
+                self.update_print_page(&path);
             }
             PatchRegistryRequest::Rebuild {
                 index_path,
                 process_cfg,
+                search_index,
+                print_page,
             } => {
-                for (_, (_, watcher)) in self.patches.drain() {
-                    watcher.send_modify(|v| *v = "__RELOAD".into())
+                for (_, patch) in self.patches.drain() {
+                    patch
+                        .sender
+                        .send_modify(|v| *v = Some(LivePatchMessage::Reload))
                 }
                 self.process_cfg = process_cfg;
+                self.rerendered.clear();
+                // NOTE: The full build just created the search index
+                // and `print.html`.
+                self.search_index_tx.send_if_modified(|v| {
+                    *v = search_index;
+                    false
+                });
+                self.print_page_tx.send_if_modified(|v| {
+                    *v = print_page.map(|print_page| *print_page);
+                    false
+                });
+                // NOTE: Milliseconds since the epoch so the IDs differ across
+                // restarts yet fit in JavaScript numbers.
+                let now = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_millis() as u64);
+                self.build_id = now.max(self.build_id + 1);
                 if let Some(index_path) = index_path {
                     self.index_path = Some(index_path.with_extension("html"));
                     debug!(?self.index_path, ?self.process_cfg, "Updated index path in patch registry.")
                 }
             }
+            PatchRegistryRequest::Error { path, message } => {
+                let error = LivePatchMessage::Error { message };
+                let show_error = |patch: &mut Patch| {
+                    // Make sure the next patch is sent to replace the error.
+                    patch.markdown.clear();
+                    patch.sender.send_modify(|v| *v = Some(error.clone()));
+                };
+                match path {
+                    Some(path) => {
+                        debug!(?path, "Registry received error.");
+                        if let Some(entry) = self.patches.get_mut(&path) {
+                            show_error(entry);
+                        }
+                    }
+                    None => {
+                        debug!("Registry received error for all paths.");
+                        self.patches.values_mut().for_each(show_error);
+                    }
+                }
+            }
+            PatchRegistryRequest::ScrollTo { path, line } => {
+                debug!(?path, line, "Registry received scroll.");
+                let scroll = LivePatchMessage::ScrollTo { line };
+                // No receivers is fine.
+                _ = self.broadcast_tx.send((Some(path), scroll));
+            }
+            PatchRegistryRequest::ScrollToChanges(scroll_to_changes) => {
+                self.scroll_to_changes = scroll_to_changes;
+            }
+            PatchRegistryRequest::Toc {
+                removed,
+                rerendered,
+                chapters,
+                print_data,
+            } => {
+                debug!(
+                    ?removed,
+                    ?rerendered,
+                    "Registry received table of contents."
+                );
+                for path in removed {
+                    if let Some(patch) = self.patches.remove(&path) {
+                        patch
+                            .sender
+                            .send_modify(|v| *v = Some(LivePatchMessage::Reload));
+                    }
+                }
+                // NOTE: Reloading keeps the patch,
+                // which the tabs get again when they reconnect.
+                for path in rerendered {
+                    if self.patches.contains_key(&path) {
+                        self.rerendered.insert(path.clone());
+                    }
+                    // No receivers is fine.
+                    _ = self
+                        .broadcast_tx
+                        .send((Some(path), LivePatchMessage::Reload));
+                }
+                self.search_index_tx.send_if_modified(|search_index| {
+                    search_index.as_mut().is_some_and(|source| {
+                        source.update_toc(&chapters);
+                        true
+                    })
+                });
+                let smart_punctuation = self.process_cfg.smart_punctuation;
+                self.print_page_tx.send_if_modified(|print_page| {
+                    print_page.as_mut().is_some_and(|print_page| {
+                        print_page.update_toc(&chapters, print_data, smart_punctuation);
+                        true
+                    })
+                });
+                // No receivers is fine.
+                _ = self.broadcast_tx.send((None, LivePatchMessage::Toc));
+            }
+            PatchRegistryRequest::PrintPage(html) => {
+                debug!("Registry received print page.");
+                let print_patch = self.patches.entry(PRINT_PATH.into()).or_default();
+                let message = LivePatchMessage::Patch {
+                    id: self.next_patch_id,
+                    html: html.clone(),
+                    changed_lines: Vec::new(),
+                };
+                self.next_patch_id += 1;
+                print_patch.sender.send_modify(|v| *v = Some(message));
+                print_patch.html = html;
+                // NOTE: `print.html` on disk is already up to date.
+                self.rerendered.insert(PRINT_PATH.into());
+            }
             PatchRegistryRequest::Clear => self.patches.clear(),
         }
         Ok(())
@@ -72,21 +276,25 @@ impl Actor for PatchRegistry {
         match msg {
             PatchRegistryQuery::Watch(path) => {
                 let path = self.resolve_index_path(path).into_owned();
-                let watch_receiver = match self.patches.entry(path) {
-                    Entry::Occupied(entry) => entry.get().1.subscribe(),
-                    Entry::Vacant(entry) => {
-                        let (sender, receiver) = watch::channel(Default::default());
-                        entry.insert((Default::default(), sender));
-                        receiver
-                    }
-                };
+                let watch_receiver = self
+                    .patches
+                    .entry(path.clone())
+                    .or_default()
+                    .sender
+                    .subscribe();
                 response_sender
-                    .send(PatchRegistryResponse::WatchReceiver(watch_receiver))
+                    .send(PatchRegistryResponse::WatchReceivers {
+                        path,
+                        build_id: self.build_id,
+                        patch_receiver: watch_receiver,
+                        broadcast_receiver: self.broadcast_tx.subscribe(),
+                    })
                     .drop_result();
             }
             PatchRegistryQuery::GetHasPatch(path) => {
                 let path = self.resolve_index_path(path);
-                let has_patch = self.patches.contains_key(path.as_path());
+                let has_patch = self.patches.contains_key(path.as_ref())
+                    && !self.rerendered.contains(path.as_ref());
                 response_sender
                     .send(PatchRegistryResponse::HasPatch(has_patch))
                     .drop_result();
@@ -99,13 +307,42 @@ impl Actor for PatchRegistry {
 /// A request to modify the patch registry.
 #[derive(Debug)]
 pub enum PatchRegistryRequest {
-    /// Register a new patch with the preprocessed Markdown content.
-    NewPatch(PathBuf, String),
-    /// The book is rebuilt, with an optional new index path.
+    /// Register a new patch with the preprocessed Markdown content and
+    /// the source line of each of its lines.
+    NewPatch(PathBuf, String, Vec<usize>),
+    /// The book is rebuilt, with an optional new index path,
+    /// and what to re-create the search index and `print.html` from
+    /// if enabled.
     Rebuild {
         index_path: Option<PathBuf>,
         process_cfg: ProcessCfg,
+        search_index: Option<SearchIndexSource>,
+        print_page: Option<Box<PrintPage>>,
+    },
+    /// Show the error `message` in place of the patch for relative HTTP `path`,
+    /// or for all paths if [`None`].
+    Error {
+        path: Option<PathBuf>,
+        message: String,
+    },
+    /// Scroll the tabs at relative HTTP `path` to
+    /// the block at zero-based `line` of the source.
+    ScrollTo { path: PathBuf, line: usize },
+    /// Update if to scroll to the first changed block after each patch.
+    ScrollToChanges(bool),
+    /// The table of contents changed to the rendered `chapters`,
+    /// the chapters at relative HTTP paths `removed` are gone,
+    /// the pages at `rerendered` are rendered anew,
+    /// and `print.html` renders with `print_data`.
+    Toc {
+        removed: Vec<PathBuf>,
+        rerendered: Vec<PathBuf>,
+        chapters: Vec<TocChapter>,
+        print_data: serde_json::Map<String, serde_json::Value>,
     },
+    /// `print.html` is re-rendered with the post-processed `<main>` body
+    /// content.
+    PrintPage(String),
     /// Clear the registry, like a soft shutdown.
     Clear,
 }
@@ -115,22 +352,42 @@ pub enum PatchRegistryRequest {
 pub enum PatchRegistryQuery {
     /// Watch a path for changes.
     Watch(PathBuf),
-    /// Get if a path has patches.
+    /// Get if a path has patches newer than its page.
     GetHasPatch(PathBuf),
 }
 
 /// A response from patch registry.
 #[derive(Debug)]
 pub enum PatchRegistryResponse {
-    /// Receiver to watch for patches.
-    WatchReceiver(watch::Receiver<String>),
-    /// If a path has patches.
+    /// Receivers to watch for patches at the resolved relative HTTP `path`
+    /// and for broadcasts, and the ID of the latest build.
+    WatchReceivers {
+        path: PathBuf,
+        build_id: u64,
+        patch_receiver: watch::Receiver<Option<LivePatchMessage>>,
+        broadcast_receiver: broadcast::Receiver<(Option<PathBuf>, LivePatchMessage)>,
+    },
+    /// If a path has patches newer than its page.
     HasPatch(bool),
 }
 
 impl PatchRegistry {
+    /// Splice the chapter at relative HTTP `path` just patched into
+    /// `print.html`, to re-render in the background.
+    fn update_print_page(&mut self, path: &Path) {
+        let Some(patch) = self.patches.get(path) else {
+            return;
+        };
+        let smart_punctuation = self.process_cfg.smart_punctuation;
+        self.print_page_tx.send_if_modified(|print_page| {
+            print_page.as_mut().is_some_and(|print_page| {
+                print_page.update_chapter(path, &patch.markdown, smart_punctuation)
+            })
+        });
+    }
+
     /// Convert HTTP `path` to the index path if it is the path to root.
-    fn resolve_index_path(&self, path: PathBuf) -> Cow<'_, PathBuf> {
+    fn resolve_index_path(&self, path: PathBuf) -> Cow<'_, Path> {
         match &self.index_path {
             Some(index_path) if path == PathBuf::new() => Cow::Borrowed(index_path),
             _ => Cow::Owned(path),
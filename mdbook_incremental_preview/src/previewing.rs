@@ -5,6 +5,7 @@ pub type IgnoredPaths = Arc<RwLock<HashSet<PathBuf>>>;
 pub struct Previewer {
     build_temp_dir: TempDir,
     book_root: Arc<Path>,
+    settings: PreviewSettings,
     socket_address: SocketAddr,
     open_browser_at: Option<PathBuf>,
     versions: HashMap<PathBuf, i32>,
@@ -15,20 +16,25 @@ pub struct Previewer {
     )>,
     rebuilder: Option<(ActorHandle<ActorMsg<Rebuilder>>, ActorRef<Rebuilder>)>,
     server: Option<JoinHandle<()>>,
+    build_report_tx: BuildReportTx,
 }
 
 impl Previewer {
-    pub fn try_new() -> Result<Self> {
+    /// Make a previewer that sends [`BuildReport`]s to `build_report_tx`,
+    /// if provided.
+    pub fn try_new(build_report_tx: BuildReportTx) -> Result<Self> {
         Ok(Self {
             build_temp_dir: tempdir()?,
             book_root: Path::new("").into(),
-            socket_address: ([127, 0, 0, 1], 3000).into(),
+            settings: Default::default(),
+            socket_address: PreviewSettings::default().socket_address,
             open_browser_at: Some("".into()),
             versions: Default::default(),
             ignored_paths: Default::default(),
             patch_registry: None,
             rebuilder: None,
             server: None,
+            build_report_tx,
         })
     }
 
@@ -43,10 +49,12 @@ impl Previewer {
             self.book_root.clone(),
             self.build_dir().into(),
             self.socket_address,
+            self.settings.debounce,
             info_tx.clone(),
             self.get_or_make_patch_registry(env),
-            self.open_browser_at.take(),
+            self.take_open_browser_at(),
             self.ignored_paths.clone(),
+            self.build_report_tx.clone(),
         );
         yield_now().await;
         let (handle, rebuilder_ref) =
@@ -66,6 +74,11 @@ impl Previewer {
         )));
     }
 
+    /// Where to open the browser at, if the settings allow.
+    fn take_open_browser_at(&mut self) -> Option<PathBuf> {
+        mem::take(&mut self.open_browser_at).filter(|_| self.settings.open_browser)
+    }
+
     fn build_dir(&self) -> &Path {
         self.build_temp_dir.path()
     }
@@ -100,8 +113,8 @@ impl Previewer {
         if let Some((_, actor_ref)) = &self.patch_registry {
             actor_ref.clone()
         } else {
-            let (handle, actor_ref) =
-                PatchRegistry::default().spawn_with_token(env.cancellation_token.child_token());
+            let (handle, actor_ref) = PatchRegistry::new(self.settings.scroll_to_changes)
+                .spawn_with_token(env.cancellation_token.child_token());
             self.patch_registry = Some((handle, actor_ref.clone()));
             actor_ref
         }
@@ -136,7 +149,9 @@ impl Actor for Previewer {
                 match &self.rebuilder {
                     Some((_, ref_)) => {
                         info!("Already started live patching; not restarting.");
-                        if let Some(open_browser_at) = mem::take(&mut self.open_browser_at) {
+                        let open_browser_at = mem::take(&mut self.open_browser_at);
+                        let open_browser = self.settings.open_browser;
+                        if let Some(open_browser_at) = open_browser_at.filter(|_| open_browser) {
                             let msg = RebuildInfo::OpenBrowser(open_browser_at);
                             ref_.cast(msg).await.drop_result();
                         }
@@ -144,6 +159,35 @@ impl Actor for Previewer {
                     None => self.start(env).await,
                 }
             }
+            PreviewInfo::Settings(settings) if settings == self.settings => {
+                debug!(?settings, "Ignoring unchanged.");
+            }
+            PreviewInfo::Settings(settings) => {
+                debug!(?settings, "Updating.");
+                let address_changed = settings.socket_address != self.settings.socket_address;
+                if address_changed {
+                    self.socket_address = settings.socket_address;
+                }
+                if let Some((_, patch_registry_ref)) = &self.patch_registry {
+                    let msg = PatchRegistryRequest::ScrollToChanges(settings.scroll_to_changes);
+                    patch_registry_ref.cast(msg).await.drop_result();
+                }
+                let debounce = settings.debounce;
+                self.settings = settings;
+                if let Some((_, rebuilder_ref)) = &self.rebuilder {
+                    match address_changed {
+                        true => {
+                            info!("Restarting live patching.");
+                            self.stop().await;
+                            self.start(env).await;
+                        }
+                        false => {
+                            let msg = RebuildInfo::Debounce(debounce);
+                            rebuilder_ref.cast(msg).await.expect("Rebuilder died.");
+                        }
+                    }
+                }
+            }
             PreviewInfo::StopPreview => {
                 info!("Stopping live patching.");
                 self.stop().await;
@@ -186,6 +230,20 @@ impl Actor for Previewer {
                     }
                 }
             }
+            PreviewInfo::ScrollTo { path, line } => match &self.rebuilder {
+                Some((_, rebuilder_ref)) => {
+                    let msg = RebuildInfo::ScrollTo { path, line };
+                    rebuilder_ref.cast(msg).await.expect("Rebuilder died.");
+                }
+                None => debug!(?path, "Ignoring scroll, without rebuilder."),
+            },
+            PreviewInfo::Saved(path) => match &self.rebuilder {
+                Some((_, rebuilder_ref)) => {
+                    let msg = RebuildInfo::Saved(path);
+                    rebuilder_ref.cast(msg).await.expect("Rebuilder died.");
+                }
+                None => debug!(?path, "Ignoring save, without rebuilder."),
+            },
             PreviewInfo::Closed(path) => {
                 debug!(?path, "Closed. Stopping ignoring its file events.");
                 self.versions.remove(&path);
@@ -221,6 +279,8 @@ pub enum PreviewInfo {
     },
     /// Stop the preview server.
     StopPreview,
+    /// Update the settings.
+    Settings(PreviewSettings),
     /// Opened path.
     Opened { path: PathBuf, version: i32 },
     /// Content of a modified path.
@@ -229,10 +289,63 @@ pub enum PreviewInfo {
         version: i32,
         content: String,
     },
+    /// Scroll the preview to the block at zero-based `line` of
+    /// the chapter at absolute `path`.
+    ScrollTo { path: PathBuf, line: usize },
+    /// Saved path, whose file events are ignored while it is open.
+    Saved(PathBuf),
     /// Closed path.
     Closed(PathBuf),
 }
 
+/// Settings of [`Previewer`] that can change while it runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreviewSettings {
+    /// Default address to serve the preview at.
+    pub socket_address: SocketAddr,
+    /// If to open the browser when starting the preview.
+    pub open_browser: bool,
+    /// How long to wait for file changes to settle before handling them.
+    pub debounce: Duration,
+    /// If to scroll to the first changed block after each patch.
+    pub scroll_to_changes: bool,
+}
+
+impl Default for PreviewSettings {
+    fn default() -> Self {
+        Self {
+            socket_address: ([127, 0, 0, 1], 3000).into(),
+            open_browser: true,
+            debounce: Duration::from_millis(20),
+            scroll_to_changes: false,
+        }
+    }
+}
+
+/// Result of building the book or request from a browser tab,
+/// reported back to the user of [`Previewer`].
+#[derive(Clone, Debug)]
+pub enum BuildReport {
+    /// The book was fully rebuilt.
+    Rebuilt,
+    /// The chapter at the absolute path was patched.
+    Patched(PathBuf),
+    /// Building failed because of the file at the absolute `path`.
+    Failed { path: PathBuf, message: String },
+    /// A browser tab asked to show zero-based `line` of
+    /// the chapter at the absolute `path`.
+    ShowSource { path: PathBuf, line: usize },
+}
+
+/// Optional sender of [`BuildReport`]s.
+pub type BuildReportTx = Option<mpsc::Sender<BuildReport>>;
+
+pub async fn send_build_report(build_report_tx: &BuildReportTx, report: BuildReport) {
+    if let Some(tx) = build_report_tx {
+        tx.send(report).await.drop_result();
+    }
+}
+
 impl Drop for Previewer {
     fn drop(&mut self) {
         self.maybe_stop_web_server();
@@ -0,0 +1,143 @@
+use super::*;
+
+/// Relative HTTP path of the print page.
+pub const PRINT_PATH: &str = "print.html";
+
+/// What `print.html` is rendered from:
+/// the rendered chapters, and the template data, Handlebars registry,
+/// and post-process configuration the full build rendered it with.
+#[derive(Clone, Debug)]
+pub struct PrintPage {
+    /// All rendered chapters in the book's order, including empty ones.
+    pub chapters: Vec<PrintChapter>,
+    /// If page breaks precede the chapters but the index.
+    pub page_break: bool,
+    pub data: serde_json::Map<String, serde_json::Value>,
+    /// Shared so cloning the page is cheap.
+    pub handlebars: Arc<Handlebars<'static>>,
+    pub process_cfg: ProcessCfg,
+    pub destination: PathBuf,
+}
+
+/// A chapter's part of the print page.
+#[derive(Clone, Debug)]
+pub struct PrintChapter {
+    /// Relative path of the chapter in the book, the `.md` file.
+    pub path: PathBuf,
+    /// If a page break precedes the chapter.
+    pub page_break: bool,
+    /// The chapter rendered with its links fixed for the print page,
+    /// shared so cloning the page is cheap.
+    pub html: Arc<str>,
+}
+
+/// Page break between chapters, as in `HtmlHandlebars::render_item`.
+pub const PAGE_BREAK: &str =
+    r#"<div style="break-before: page; page-break-before: always;"></div>"#;
+
+impl PrintPage {
+    /// Re-render the chapter at relative HTTP `path` from
+    /// the preprocessed `markdown`.
+    /// Returns if the chapter is on the print page.
+    pub fn update_chapter(&mut self, path: &Path, markdown: &str, smart_punctuation: bool) -> bool {
+        let Some(chapter) = self
+            .chapters
+            .iter_mut()
+            .find(|chapter| chapter.path.with_extension("html") == path)
+        else {
+            return false;
+        };
+        chapter.html =
+            utils::render_markdown_with_path(markdown, smart_punctuation, Some(&chapter.path))
+                .into();
+        true
+    }
+
+    /// Keep the `chapters` still in the book in their order,
+    /// render the added ones, and update the template `data`.
+    pub fn update_toc(
+        &mut self,
+        chapters: &[TocChapter],
+        data: serde_json::Map<String, serde_json::Value>,
+        smart_punctuation: bool,
+    ) {
+        let mut old_chapters: HashMap<PathBuf, PrintChapter> = mem::take(&mut self.chapters)
+            .into_iter()
+            .map(|chapter| (chapter.path.clone(), chapter))
+            .collect();
+        for chapter in chapters {
+            let print_chapter = match (old_chapters.remove(&chapter.path), &chapter.added_markdown)
+            {
+                (Some(old_chapter), _) => old_chapter,
+                (None, Some(markdown)) => PrintChapter {
+                    path: chapter.path.clone(),
+                    page_break: self.page_break,
+                    html: utils::render_markdown_with_path(
+                        markdown,
+                        smart_punctuation,
+                        Some(&chapter.path),
+                    )
+                    .into(),
+                },
+                // NOTE: The full build did not render the chapter.
+                (None, None) => continue,
+            };
+            self.chapters.push(print_chapter);
+        }
+        self.data = data;
+    }
+
+    /// The `<main>` body content before post-processing.
+    pub fn content(&self) -> String {
+        let mut content = String::new();
+        for chapter in &self.chapters {
+            if chapter.page_break {
+                content.push_str(PAGE_BREAK);
+            }
+            content.push_str(&chapter.html);
+        }
+        content
+    }
+
+    /// Render the whole page, write it to the build directory,
+    /// and return the post-processed `<main>` body content to patch tabs with.
+    pub fn write(mut self) -> Result<String> {
+        let content = self.content();
+        self.data.insert("content".into(), json!(content));
+        let rendered = self.handlebars.render("index", &self.data)?;
+        let rendered = self.process_cfg.post_process(rendered);
+        utils::fs::write_file(&self.destination, PRINT_PATH, rendered.as_bytes())?;
+        Ok(self.process_cfg.post_process(content))
+    }
+}
+
+/// Re-render `print.html` from the latest [`PrintPage`] each time `page_rx`
+/// changes, and hand the new content to the patch registry at `registry_ref`
+/// to patch the tabs showing it.
+///
+/// NOTE: The watch channel coalesces the patches made while writing,
+/// so writes never pile up or block other patches.
+pub async fn rewrite_print_page_continuously(
+    mut page_rx: watch::Receiver<Option<PrintPage>>,
+    registry_ref: ActorRef<PatchRegistry>,
+) {
+    while page_rx.changed().await.is_ok() {
+        let Some(print_page) = page_rx.borrow_and_update().clone() else {
+            continue;
+        };
+        debug!(?print_page.destination, "Re-rendering the print page.");
+        match spawn_blocking(move || print_page.write()).await {
+            Ok(Ok(html)) => {
+                if registry_ref
+                    .cast(PatchRegistryRequest::PrintPage(html))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+            Ok(Err(err)) => error!(?err, "Writing the print page."),
+            Err(err) => error!(?err, "Joining the print page task."),
+        }
+    }
+}
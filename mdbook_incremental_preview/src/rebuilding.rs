@@ -7,10 +7,12 @@ pub struct Rebuilder {
     book_root: Arc<Path>,
     build_dir: Arc<Path>,
     socket_address: SocketAddr,
+    debounce: Duration,
     info_tx: mpsc::Sender<ServeInfo>,
     patch_registry_ref: ActorRef<PatchRegistry>,
     book_toml: PathBuf,
     src_dir: Arc<Path>,
+    build_report_tx: BuildReportTx,
     mutables: RebuilderMut,
 }
 
@@ -29,11 +31,23 @@ impl Actor for Rebuilder {
         match msg {
             RebuildInfo::Rebuild(reload) => {
                 info!(?self.build_dir, "Full rebuild.");
+                self.mutables.book_built = false;
+                self.stop_patching_summary();
+                let summary_md = match &self.mutables.summary_md {
+                    // We have not loaded the book yet, so guess the default.
+                    summary_md if *summary_md == PathBuf::default() => {
+                        BookPaths::new(&self.book_root, &Config::default()).summary_md
+                    }
+                    summary_md => summary_md.clone(),
+                };
                 _ = self.mutables.rebuild_join_set.spawn(load_book(
                     self.book_root.clone(),
                     self.build_dir.clone(),
                     reload,
                     env.clone(),
+                    summary_md,
+                    self.patch_registry_ref.clone(),
+                    self.build_report_tx.clone(),
                 ));
             }
             RebuildInfo::NewBook(data) => {
@@ -42,12 +56,14 @@ impl Actor for Rebuilder {
                     reload,
                     html_config,
                     theme_dir,
-                    hbs_state,
+                    mut hbs_state,
                 } = *data;
                 self.patch_registry_ref
                     .cast(PatchRegistryRequest::Rebuild {
                         index_path: hbs_state.index_path.clone(),
                         process_cfg: hbs_state.process_cfg.clone(),
+                        search_index: hbs_state.search_index.take(),
+                        print_page: hbs_state.print_page.take().map(Box::new),
                     })
                     .await
                     .context("Clearing the patch registry")?;
@@ -58,6 +74,7 @@ impl Actor for Rebuilder {
                 let m = &mut self.mutables;
                 (m.book, m.html_config, m.theme_dir, m.hbs_state) =
                     (book.into(), html_config, theme_dir, hbs_state);
+                m.book_built = true;
                 // Re-patch the chapters patched after a rebuild.
                 let paths = running_patch_join_sets(&mut m.patch_join_sets);
                 let (env, msg) = (env.clone(), RebuildInfo::ChangedPaths(paths));
@@ -66,6 +83,7 @@ impl Actor for Rebuilder {
             RebuildInfo::ChangedPaths(paths) => {
                 info!(?paths, "Directories changed.");
                 let m = &mut self.mutables;
+                let summary_changed = paths.contains(&m.summary_md);
                 let full_rebuild = match &m.maybe_gitignore {
                     Some((_, gitignore_path)) if paths.contains(gitignore_path) => {
                         // Gitignore file changed,
@@ -78,8 +96,9 @@ impl Actor for Rebuilder {
                     // `book.toml` changed, make a full rebuild,
                     // reload the watcher and the server.
                     _ if paths.contains(&self.book_toml) => Some(true),
-                    // `Summary.md` or theme changed, make a full rebuild.
-                    _ if paths.contains(&m.summary_md)
+                    // Theme changed, or `Summary.md` changed without
+                    // a successful full build to patch, make a full rebuild.
+                    _ if (summary_changed && !m.book_built)
                         || paths.iter().any(|path| path.starts_with(&m.theme_dir)) =>
                     {
                         Some(false)
@@ -91,15 +110,79 @@ impl Actor for Rebuilder {
                 match full_rebuild {
                     Some(reload) => self.send_rebuild_info(env.clone(), reload),
                     None => {
-                        let (b, ref_, sets) =
-                            (&m.book, &self.patch_registry_ref, &mut m.patch_join_sets);
-                        m.hbs_state.patch(b, &self.src_dir, paths, ref_, sets).await;
+                        if summary_changed {
+                            self.patch_summary(SummarySource::Disk, env.clone());
+                        }
+                        let m = &mut self.mutables;
+                        let (b, ref_, tx, sets) = (
+                            &m.book,
+                            &self.patch_registry_ref,
+                            &self.build_report_tx,
+                            &mut m.patch_join_sets,
+                        );
+                        m.hbs_state
+                            .patch(b, &self.src_dir, paths, ref_, tx, sets)
+                            .await;
+                    }
+                }
+            }
+            RebuildInfo::NewSummary { patch_id, outcome } => {
+                let m = &mut self.mutables;
+                if patch_id != m.summary_patch_id {
+                    debug!(patch_id, "Ignoring stale summary patch.");
+                    return Ok(());
+                }
+                m.patching_summary = false;
+                let summary_md = m.summary_md.clone();
+                match outcome {
+                    SummaryOutcome::Patched(data) => {
+                        let SummaryData {
+                            path2ctxs,
+                            removed,
+                            rerendered,
+                            chapters,
+                            print_data,
+                        } = *data;
+                        m.hbs_state.path2ctxs = path2ctxs;
+                        let msg = PatchRegistryRequest::Toc {
+                            removed,
+                            rerendered,
+                            chapters,
+                            print_data,
+                        };
+                        self.patch_registry_ref
+                            .cast(msg)
+                            .await
+                            .context("Updating the table of contents in the patch registry")?;
+                        let report = BuildReport::Patched(summary_md);
+                        send_build_report(&self.build_report_tx, report).await;
+                    }
+                    SummaryOutcome::Rebuild => {
+                        m.pending_summary = None;
+                        self.send_rebuild_info(env.clone(), false);
+                        return Ok(());
                     }
+                    SummaryOutcome::Failed(message) => {
+                        let report = BuildReport::Failed {
+                            path: summary_md,
+                            message,
+                        };
+                        send_build_report(&self.build_report_tx, report).await;
+                    }
+                }
+                if let Some(source) = self.mutables.pending_summary.take() {
+                    self.patch_summary(source, env.clone());
                 }
             }
             RebuildInfo::ModifiedContent { path, content } => {
                 let m = &mut self.mutables;
-                if let Some((arc_path, ctx)) = m.hbs_state.path2ctxs.get_key_value(path.as_path()) {
+                if path == m.summary_md {
+                    if m.book_built {
+                        self.patch_summary(SummarySource::Editor(content), env.clone());
+                    }
+                } else if let Some((arc_path, ctx)) =
+                    m.hbs_state.path2ctxs.get_key_value(path.as_path())
+                {
                     let task = patch_chapter_w_content(
                         arc_path.clone(),
                         self.src_dir.clone(),
@@ -107,14 +190,59 @@ impl Actor for Rebuilder {
                         content,
                         m.book.clone(),
                         self.patch_registry_ref.clone(),
+                        self.build_report_tx.clone(),
                     );
                     _ = m.patch_join_sets.entry(path).or_default().spawn(task);
                 }
             }
+            RebuildInfo::Saved(path) if path == self.mutables.summary_md => {
+                match self.mutables.book_built {
+                    true => self.patch_summary(SummarySource::Disk, env.clone()),
+                    false => self.send_rebuild_info(env.clone(), false),
+                }
+            }
+            // NOTE: The other saved files were patched as they were modified.
+            RebuildInfo::Saved(_) => {}
+            RebuildInfo::ScrollTo { path, line } => {
+                let Ok(relative_path) = path.strip_prefix(&self.src_dir) else {
+                    debug!(?path, "Ignoring scroll outside the source directory.");
+                    return Ok(());
+                };
+                let msg = PatchRegistryRequest::ScrollTo {
+                    path: relative_path.with_extension("html"),
+                    line,
+                };
+                self.mark_source_lines(path).await;
+                self.patch_registry_ref.cast(msg).await.drop_result();
+            }
+            RebuildInfo::Connected(http_path) => {
+                if let Some(path) = self.source_path(&http_path) {
+                    self.mark_source_lines(path).await;
+                }
+            }
+            RebuildInfo::ShowSource { http_path, line } => match self.source_path(&http_path) {
+                Some(path) => {
+                    let report = BuildReport::ShowSource { path, line };
+                    send_build_report(&self.build_report_tx, report).await;
+                }
+                None => debug!(?http_path, "Ignoring request to show unknown source."),
+            },
             RebuildInfo::OpenBrowser(path) => {
                 self.mutables.open_browser_at = Some(path);
                 self.maybe_open_browser();
             }
+            RebuildInfo::Debounce(debounce) if debounce == self.debounce => {}
+            RebuildInfo::Debounce(debounce) => {
+                debug!(?debounce, "Updating.");
+                self.debounce = debounce;
+                let m = &self.mutables;
+                if m._debouncer_to_keep_watcher_alive.is_some() {
+                    let (src_dir, theme_dir) = (self.src_dir.clone(), m.theme_dir.clone());
+                    let extra_watch_dirs = m.book.config.build.extra_watch_dirs.clone();
+                    self.watch(&src_dir, &theme_dir, &extra_watch_dirs, env)
+                        .await;
+                }
+            }
         }
         Ok(())
     }
@@ -125,12 +253,28 @@ pub enum RebuildInfo {
     Rebuild(bool),
     /// Newly built book and state.
     NewBook(Box<BookData>),
+    /// Outcome of the summary patch of `patch_id`.
+    NewSummary {
+        patch_id: u64,
+        outcome: SummaryOutcome,
+    },
     /// Paths changed.
     ChangedPaths(Vec<PathBuf>),
     /// Content of a modified path.
     ModifiedContent { path: PathBuf, content: String },
+    /// The opened file at the absolute path was saved.
+    Saved(PathBuf),
+    /// Scroll to the block at zero-based `line` of the chapter at absolute `path`.
+    ScrollTo { path: PathBuf, line: usize },
+    /// A browser tab connected to the relative HTTP path.
+    Connected(PathBuf),
+    /// A browser tab at relative `http_path` asked to show
+    /// zero-based `line` of the chapter's source.
+    ShowSource { http_path: PathBuf, line: usize },
     /// Open the browser for the chapter of the given absolute path.
     OpenBrowser(PathBuf),
+    /// Update how long to wait for file changes to settle.
+    Debounce(Duration),
 }
 
 impl Rebuilder {
@@ -142,7 +286,11 @@ impl Rebuilder {
         env: &ActorRef<Self>,
     ) -> Result<()> {
         let m = &mut self.mutables;
-        let src_dir = book.root.join(&book.config.book.src);
+        let BookPaths {
+            src_dir,
+            summary_md,
+            ..
+        } = BookPaths::new(&book.root, &book.config);
         let src_dir_changed = src_dir != *self.src_dir;
         let theme_dir_changed = m.theme_dir != *theme_dir;
         let extra_watch_dirs_changed =
@@ -164,40 +312,11 @@ impl Rebuilder {
         yield_now().await;
 
         if src_dir_changed || theme_dir_changed || extra_watch_dirs_changed {
-            info!(
-                ?self.book_root,
-                ?src_dir,
-                ?theme_dir,
-                ?book.config.build.extra_watch_dirs,
-                "Reloading the file watcher.",
-            );
-            let (env, ignored_paths) = (env.clone(), m.ignored_paths.clone());
-            let event_handler = move |events: Result<Vec<DebouncedEvent>, _>| match events {
-                Ok(events) if !events.is_empty() => {
-                    let paths = events.into_iter().map(|event| event.path);
-                    let paths = {
-                        let ignored_paths = ignored_paths.read().unwrap();
-                        paths.filter(|path| !ignored_paths.contains(path)).collect()
-                    };
-                    env.blocking_cast(RebuildInfo::ChangedPaths(paths))
-                        .drop_result();
-                }
-                Ok(_) => {}
-                Err(err) => error!(?err, "Watching for changes"),
-            };
-            let watch = || {
-                watch_file_changes(
-                    &self.book_root,
-                    &src_dir,
-                    theme_dir,
-                    &self.book_toml,
-                    &book.config.build.extra_watch_dirs,
-                    event_handler,
-                )
-            };
-            m._debouncer_to_keep_watcher_alive = Some(block_n_yield(watch).await);
+            let extra_watch_dirs = &book.config.build.extra_watch_dirs;
+            self.watch(&src_dir, theme_dir, extra_watch_dirs, env).await;
         }
 
+        let m = &mut self.mutables;
         if src_dir_changed || additional_js_changed || additional_css_changed || file_404_changed {
             let input_404 = book
                 .config
@@ -226,12 +345,117 @@ impl Rebuilder {
         }
 
         if src_dir_changed {
-            (m.summary_md, self.src_dir) = (src_dir.join("SUMMARY.md"), src_dir.into());
+            (m.summary_md, self.src_dir) = (summary_md, src_dir.into());
         }
         self.maybe_open_browser();
         Ok(())
     }
 
+    /// (Re)start watching the files of the book.
+    async fn watch(
+        &mut self,
+        src_dir: &Path,
+        theme_dir: &Path,
+        extra_watch_dirs: &[PathBuf],
+        env: &ActorRef<Self>,
+    ) {
+        info!(
+            ?self.book_root,
+            ?src_dir,
+            ?theme_dir,
+            ?extra_watch_dirs,
+            ?self.debounce,
+            "Reloading the file watcher.",
+        );
+        let m = &mut self.mutables;
+        let (env, ignored_paths) = (env.clone(), m.ignored_paths.clone());
+        let event_handler = move |events: Result<Vec<DebouncedEvent>, _>| match events {
+            Ok(events) if !events.is_empty() => {
+                let paths = events.into_iter().map(|event| event.path);
+                let paths = {
+                    let ignored_paths = ignored_paths.read().unwrap();
+                    paths.filter(|path| !ignored_paths.contains(path)).collect()
+                };
+                env.blocking_cast(RebuildInfo::ChangedPaths(paths))
+                    .drop_result();
+            }
+            Ok(_) => {}
+            Err(err) => error!(?err, "Watching for changes"),
+        };
+        let watch = || {
+            watch_file_changes(
+                &self.book_root,
+                src_dir,
+                theme_dir,
+                &self.book_toml,
+                extra_watch_dirs,
+                self.debounce,
+                event_handler,
+            )
+        };
+        m._debouncer_to_keep_watcher_alive = Some(block_n_yield(watch).await);
+    }
+
+    /// Patch the chapter at absolute `path` unless it is already patched,
+    /// because only patches mark the source lines of their blocks.
+    async fn mark_source_lines(&mut self, path: PathBuf) {
+        let m = &mut self.mutables;
+        if !m.patch_join_sets.contains_key(&path) {
+            let (b, ref_, tx, sets) = (
+                &m.book,
+                &self.patch_registry_ref,
+                &self.build_report_tx,
+                &mut m.patch_join_sets,
+            );
+            m.hbs_state
+                .patch(b, &self.src_dir, [path], ref_, tx, sets)
+                .await;
+        }
+    }
+
+    /// Absolute path of the chapter served at relative `http_path`.
+    fn source_path(&self, http_path: &Path) -> Option<PathBuf> {
+        self.mutables
+            .hbs_state
+            .path2ctxs
+            .keys()
+            .find(|path| {
+                path.strip_prefix(&self.src_dir)
+                    .is_ok_and(|relative_path| relative_path.with_extension("html") == http_path)
+            })
+            .map(|path| path.to_path_buf())
+    }
+
+    /// Patch the book for the changed `SUMMARY.md` from `source` in
+    /// the background, after the summary patch running, if any.
+    fn patch_summary(&mut self, source: SummarySource, env: ActorRef<Self>) {
+        let m = &mut self.mutables;
+        if m.patching_summary {
+            m.pending_summary = Some(source);
+            return;
+        }
+        m.patching_summary = true;
+        m.summary_patch_id += 1;
+        _ = m.summary_join_set.spawn(patch_summary(
+            m.summary_patch_id,
+            source,
+            m.book.clone(),
+            self.src_dir.clone(),
+            self.build_dir.clone(),
+            m.hbs_state.path2ctxs.clone(),
+            m.hbs_state.index_path.clone(),
+            env,
+        ));
+    }
+
+    /// Cancel the summary patches, whose results the full rebuild replaces.
+    fn stop_patching_summary(&mut self) {
+        let m = &mut self.mutables;
+        m.summary_join_set.abort_all();
+        (m.patching_summary, m.pending_summary) = (false, None);
+        m.summary_patch_id += 1;
+    }
+
     fn send_rebuild_info(&mut self, env: ActorRef<Self>, reload: bool) {
         spawn(async move {
             env.cast(RebuildInfo::Rebuild(reload)).await.drop_result();
@@ -253,24 +477,29 @@ impl Rebuilder {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         book_root: Arc<Path>,
         build_dir: Arc<Path>,
         socket_address: SocketAddr,
+        debounce: Duration,
         info_tx: mpsc::Sender<ServeInfo>,
         patch_registry_ref: ActorRef<PatchRegistry>,
         open_browser_at: Option<PathBuf>,
         ignored_paths: IgnoredPaths,
+        build_report_tx: BuildReportTx,
     ) -> Self {
-        let book_toml = book_root.join("book.toml");
+        let book_toml = BookPaths::new(&book_root, &Config::default()).book_toml;
         Self {
             book_root,
             build_dir,
             socket_address,
+            debounce,
             info_tx,
             patch_registry_ref,
             book_toml,
             src_dir: Path::new("").into(),
+            build_report_tx,
             mutables: RebuilderMut {
                 open_browser_at,
                 ignored_paths,
@@ -287,6 +516,7 @@ pub async fn patch_chapter_w_content(
     content: String,
     book: Arc<MDBookCore>,
     patch_registry_ref: ActorRef<PatchRegistry>,
+    build_report_tx: BuildReportTx,
 ) {
     let task = try_patch_chapter_w_content(
         &path,
@@ -297,7 +527,8 @@ pub async fn patch_chapter_w_content(
         &patch_registry_ref,
     );
 
-    if let Err(err) = task.await {
+    let result = task.await;
+    if let Err(err) = &result {
         error!(
             ?err,
             ?path,
@@ -305,6 +536,44 @@ pub async fn patch_chapter_w_content(
             "Patching chapter with content.",
         );
     }
+    report_patch_result(
+        result,
+        &path,
+        &src_dir,
+        &patch_registry_ref,
+        &build_report_tx,
+    )
+    .await;
+}
+
+/// Report the `result` of patching the chapter at absolute `path` to
+/// the user of [`Previewer`] and, on failure, to the browser tabs.
+pub async fn report_patch_result(
+    result: Result<()>,
+    path: &Path,
+    src_dir: &Path,
+    patch_registry_ref: &ActorRef<PatchRegistry>,
+    build_report_tx: &BuildReportTx,
+) {
+    let message = match result {
+        Ok(()) => {
+            let report = BuildReport::Patched(path.to_owned());
+            return send_build_report(build_report_tx, report).await;
+        }
+        Err(err) => format!("{err:#}"),
+    };
+    if let Ok(relative_path) = path.strip_prefix(src_dir) {
+        let msg = PatchRegistryRequest::Error {
+            path: Some(relative_path.with_extension("html")),
+            message: message.clone(),
+        };
+        patch_registry_ref.cast(msg).await.drop_result();
+    }
+    let report = BuildReport::Failed {
+        path: path.to_owned(),
+        message,
+    };
+    send_build_report(build_report_tx, report).await;
 }
 
 /// Paths of the chapters that are being patched.
@@ -323,10 +592,43 @@ async fn load_book(
     build_dir: Arc<Path>,
     reload: bool,
     env: ActorRef<Rebuilder>,
+    summary_md: PathBuf,
+    patch_registry_ref: ActorRef<PatchRegistry>,
+    build_report_tx: BuildReportTx,
 ) {
-    if let Err(err) = try_load_book(&book_root, &build_dir, reload, Default::default(), env).await {
-        error!(?err, "loading and preprocessing the book.");
-    }
+    let err = match try_load_book(&book_root, &build_dir, reload, Default::default(), env).await {
+        Ok(()) => return send_build_report(&build_report_tx, BuildReport::Rebuilt).await,
+        Err(err) => err,
+    };
+    error!(?err, "loading and preprocessing the book.");
+    let message = format!("{err:#}");
+    let msg = PatchRegistryRequest::Error {
+        path: None,
+        message: message.clone(),
+    };
+    patch_registry_ref.cast(msg).await.drop_result();
+    let path = match blames_summary(&err) {
+        true => summary_md,
+        false => book_root.join("book.toml"),
+    };
+    let report = BuildReport::Failed { path, message };
+    send_build_report(&build_report_tx, report).await;
+}
+
+/// If `err` from loading the book is caused by `SUMMARY.md`;
+/// otherwise, we blame `book.toml`,
+/// which also configures the preprocessors and renderers.
+fn blames_summary(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        let cause = cause.to_string();
+        [
+            "SUMMARY.md",
+            "Summary parsing failed",
+            "Chapter file not found",
+        ]
+        .iter()
+        .any(|pattern| cause.contains(pattern))
+    })
 }
 
 async fn try_load_book(
@@ -383,7 +685,18 @@ pub struct RebuilderMut {
     theme_dir: PathBuf,
     html_config: HtmlConfig,
     hbs_state: HtmlHbsState,
+    /// If the latest full rebuild succeeded,
+    /// so `SUMMARY.md` changes can be patched.
+    book_built: bool,
     rebuild_join_set: TwoJoinSet<()>,
+    /// The summary patch running, apart from the rebuilds.
+    summary_join_set: TwoJoinSet<()>,
+    /// ID of the latest summary patch, to ignore the stale ones.
+    summary_patch_id: u64,
+    /// If a summary patch is running, so the next one waits.
+    patching_summary: bool,
+    /// The latest summary change waiting for the summary patch running.
+    pending_summary: Option<SummarySource>,
     /// [`TwoJoinSet`]s of each patched chapter's absolute path.
     patch_join_sets: PatchJoinSets,
     ignored_paths: IgnoredPaths,
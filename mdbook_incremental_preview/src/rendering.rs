@@ -6,12 +6,19 @@ pub struct HtmlHbsState {
     pub process_cfg: ProcessCfg,
     /// Relative path of the source file of the index chapter.
     pub index_path: Option<PathBuf>,
+    /// What to re-create the search index from, if search is enabled.
+    pub search_index: Option<SearchIndexSource>,
+    /// What to re-render `print.html` from, if printing is enabled.
+    pub print_page: Option<PrintPage>,
 }
 
 #[derive(Clone, Debug)]
 pub struct CtxCore {
     pub chapter_name: Arc<str>,
     pub len_content: usize,
+    /// Position among the rendered chapters,
+    /// for their previous and next chapter links.
+    pub position: usize,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -23,6 +30,15 @@ pub struct ProcessCfg {
 }
 
 impl ProcessCfg {
+    pub fn new(html_config: &HtmlConfig, edition: Option<RustEdition>) -> Self {
+        Self {
+            smart_punctuation: html_config.smart_punctuation(),
+            playground_config: html_config.playground.clone(),
+            code_config: html_config.code.clone(),
+            edition,
+        }
+    }
+
     pub fn render_markdown(&self, markdown: &str) -> String {
         utils::render_markdown(markdown, self.smart_punctuation)
     }
@@ -35,10 +51,20 @@ impl ProcessCfg {
             self.edition,
         )
     }
+
+    /// Mark the blocks in `markdown` with their `source_lines` to
+    /// render and then [`annotate_source_lines`].
+    pub fn mark_source_lines(&self, markdown: &str, source_lines: &[usize]) -> String {
+        mark_source_lines(markdown, source_lines, self.smart_punctuation)
+    }
 }
 
 pub const RENDERER: HtmlHandlebars = HtmlHandlebars {};
 
+/// Name of mdBook's preprocessor that expands `{{#include}}`s and the like.
+// NOTE: Same as `LinkPreprocessor::NAME`, which is private.
+pub const LINKS_PREPROCESSOR_NAME: &str = "links";
+
 // NOTE: Below is adapted from
 // <https://github.com/rust-lang/mdBook/blob/3bdcc0a5a6f3c85dd751350774261dbc357b02bd/src/renderer/html_handlebars/hbs_renderer.rs>.
 
@@ -96,7 +122,7 @@ impl HtmlHbsState {
         ctx: RenderContext,
         html_config: HtmlConfig,
         theme: &Theme,
-        handlebars: &Handlebars<'_>,
+        handlebars: &Handlebars<'static>,
     ) -> Result<()> {
         info!("Running the html backend for a full render.");
         let book_config = &ctx.config.book;
@@ -117,6 +143,7 @@ impl HtmlHbsState {
 
         // Print version
         let mut print_content = String::new();
+        let mut print_chapters = Vec::new();
 
         fs::create_dir_all(destination)
             .await
@@ -161,10 +188,28 @@ impl HtmlHbsState {
             };
             // Only the first non-draft chapter item should be treated as the "index"
             is_index = false;
+            let print_start = print_content.len();
             block_n_yield(|| RENDERER.render_item(item, ctx, &mut print_content)).await?;
+            // NOTE: Keep empty chapters too so their patches find them.
+            if let BookItem::Chapter(Chapter {
+                path: Some(path), ..
+            }) = item
+            {
+                let html = &print_content[print_start..];
+                let (page_break, html) = match html.strip_prefix(PAGE_BREAK) {
+                    Some(html) => (true, html),
+                    None => (false, html),
+                };
+                print_chapters.push(PrintChapter {
+                    path: path.clone(),
+                    page_break,
+                    html: html.into(),
+                });
+            }
             let ctx = CtxCore {
                 chapter_name: name.clone().into(),
                 len_content: content.len(),
+                position: self.path2ctxs.len(),
             };
             self.path2ctxs.insert(source_path.into(), ctx);
         }
@@ -202,25 +247,28 @@ impl HtmlHbsState {
             block_n_yield(|| utils::fs::write_file(destination, "print.html", rendered.as_bytes()))
                 .await?;
             debug!("Created print.html ✓");
-        }
 
-        debug!("Render toc");
-        {
-            let rendered_toc = handlebars.render("toc_js", &data)?;
-            utils::fs::write_file(destination, "toc.js", rendered_toc.as_bytes())?;
-            debug!("Creating toc.js ✓");
-            data.insert("is_toc_html".to_owned(), json!(true));
-            let rendered_toc = handlebars.render("toc_html", &data)?;
-            utils::fs::write_file(destination, "toc.html", rendered_toc.as_bytes())?;
-            debug!("Creating toc.html ✓");
-            data.remove("is_toc_html");
+            let mut data = data.clone();
+            data.remove("content");
+            self.print_page = Some(PrintPage {
+                chapters: print_chapters,
+                page_break: html_config.print.page_break,
+                data,
+                handlebars: Arc::new(handlebars.clone()),
+                process_cfg: ProcessCfg::new(&html_config, ctx.config.rust.edition),
+                destination: destination.clone(),
+            });
         }
 
+        block_n_yield(|| render_toc(handlebars, &mut data, destination)).await?;
+
         // Render search index
         let search = html_config.search.clone().unwrap_or_default();
         if search.enable {
             debug!("Search indexing");
             block_n_yield(|| search::create_files(&search, destination, book)).await?;
+            let search_index = SearchIndexSource::new(search, destination.clone(), book);
+            self.search_index = Some(search_index);
         }
 
         debug!("Emitting redirects");
@@ -231,12 +279,7 @@ impl HtmlHbsState {
         .context("Unable to emit redirects")?;
 
         // Save post-process configuration.
-        self.process_cfg = ProcessCfg {
-            smart_punctuation: html_config.smart_punctuation(),
-            playground_config: html_config.playground,
-            code_config: html_config.code,
-            edition: ctx.config.rust.edition,
-        };
+        self.process_cfg = ProcessCfg::new(&html_config, ctx.config.rust.edition);
 
         Ok(())
     }
@@ -256,6 +299,7 @@ impl HtmlHbsState {
         src_dir: &Arc<Path>,
         paths: I,
         patch_registry_ref: &ActorRef<PatchRegistry>,
+        build_report_tx: &BuildReportTx,
         patch_join_sets: &mut PatchJoinSets,
     ) {
         for path in paths.into_iter() {
@@ -266,6 +310,7 @@ impl HtmlHbsState {
                     book.clone(),
                     src_dir.clone(),
                     patch_registry_ref.clone(),
+                    build_report_tx.clone(),
                 );
                 _ = patch_join_sets.entry(path).or_default().spawn(task);
             };
@@ -273,15 +318,35 @@ impl HtmlHbsState {
     }
 }
 
+/// Render `toc.js` and `toc.html` for the sidebar.
+pub fn render_toc(
+    handlebars: &Handlebars<'_>,
+    data: &mut serde_json::Map<String, serde_json::Value>,
+    destination: &Path,
+) -> Result<()> {
+    debug!("Render toc");
+    let rendered_toc = handlebars.render("toc_js", &data)?;
+    utils::fs::write_file(destination, "toc.js", rendered_toc.as_bytes())?;
+    debug!("Creating toc.js ✓");
+    data.insert("is_toc_html".to_owned(), json!(true));
+    let rendered_toc = handlebars.render("toc_html", &data)?;
+    utils::fs::write_file(destination, "toc.html", rendered_toc.as_bytes())?;
+    debug!("Creating toc.html ✓");
+    data.remove("is_toc_html");
+    Ok(())
+}
+
 pub async fn patch_chapter(
     path: Arc<Path>,
     CtxCore {
         chapter_name,
         len_content,
+        ..
     }: CtxCore,
     book: Arc<MDBookCore>,
     src_dir: Arc<Path>,
     patch_registry_ref: ActorRef<PatchRegistry>,
+    build_report_tx: BuildReportTx,
 ) {
     let task = try_patch_chapter(
         &path,
@@ -291,7 +356,8 @@ pub async fn patch_chapter(
         &book,
         &patch_registry_ref,
     );
-    if let Err(err) = task.await {
+    let result = task.await;
+    if let Err(err) = &result {
         error!(
             ?err,
             ?path,
@@ -299,6 +365,14 @@ pub async fn patch_chapter(
             "Patching chapter.",
         );
     }
+    report_patch_result(
+        result,
+        &path,
+        &src_dir,
+        &patch_registry_ref,
+        &build_report_tx,
+    )
+    .await;
 }
 
 pub async fn try_patch_chapter(
@@ -338,24 +412,31 @@ pub async fn try_patch_chapter_w_content(
     );
     yield_now().await;
     let chapter = Chapter::new(chapter_name, content, relative_path, vec![]);
-    let mut patcher_book = Book::new();
-    patcher_book.sections = vec![BookItem::Chapter(chapter)];
-    let (mut preprocessed_book, _) = book.preprocess_book(patcher_book).await?;
-    let markdown = match preprocessed_book.sections.pop() {
-        None => bail!("{chapter_name} at {relative_path:?} preprocessed to an empty book."),
-        Some(BookItem::Chapter(Chapter {
+    let content = chapter.content.clone();
+    let (preprocessed_chapter, maybe_expansions) = book.preprocess_chapter(chapter).await?;
+    // NOTE: If preprocessors before `links` changed the content,
+    // its lines no longer map to the source lines.
+    let source_lines = match maybe_expansions {
+        Some(expansions) if expansions.content == content => {
+            expanded_source_lines(&content, &expansions.directives, &expansions.expansions)
+        }
+        _ => vec![],
+    };
+    let markdown = match preprocessed_chapter {
+        Chapter {
             content,
             source_path: Some(source_path),
             ..
-        })) if source_path == relative_path => content,
-        _ => bail!(
-            "{chapter_name} at {relative_path:?} preprocessed to unexpected {preprocessed_book:?}"
-        ),
+        } if source_path == relative_path => content,
+        chapter => {
+            bail!("{chapter_name} at {relative_path:?} preprocessed to unexpected {chapter:?}")
+        }
     };
     patch_registry_ref
         .cast(PatchRegistryRequest::NewPatch(
             relative_path.with_extension("html"),
             markdown,
+            source_lines,
         ))
         .await
         .context("Updating the patch registry")?;
@@ -393,16 +474,8 @@ pub struct MDBookCore {
 impl MDBookCore {
     /// Run preprocessors on `book` and return the final book.
     pub async fn preprocess_book(&self, book: Book) -> Result<(Book, PreprocessorContext)> {
-        let preprocess_ctx = PreprocessorContext {
-            root: self.root.clone(),
-            config: self.config.clone(),
-            renderer: RENDERER.name().to_string(),
-            mdbook_version: MDBOOK_VERSION.to_string(),
-            chapter_titles: RefCell::new(HashMap::new()),
-            __non_exhaustive: (),
-        };
         // NOTE: This `Mutex` is needed because `PreprocessorContext: !Send`.
-        let preprocess_ctx = Mutex::new(preprocess_ctx);
+        let preprocess_ctx = Mutex::new(self.preprocess_ctx());
         let mut preprocessed_book = book;
         for preprocessor in &self.preprocessors {
             let should_run = || preprocessor_should_run(&**preprocessor, &RENDERER, &self.config);
@@ -414,7 +487,129 @@ impl MDBookCore {
         }
         Ok((preprocessed_book, preprocess_ctx.into_inner().unwrap()))
     }
+
+    /// Run preprocessors on `chapter` alone and return the final chapter,
+    /// and what the links preprocessor expanded if it ran.
+    ///
+    /// NOTE: The links preprocessor runs on the chapter split at
+    /// the directives in its content then, one chapter per piece,
+    /// so the same run tells what each directive expands to,
+    /// e.g., to map source lines.
+    pub async fn preprocess_chapter(
+        &self,
+        chapter: Chapter,
+    ) -> Result<(Chapter, Option<LinkExpansions>)> {
+        let preprocess_ctx = Mutex::new(self.preprocess_ctx());
+        let mut maybe_expansions = None;
+        let mut book = Book::new();
+        book.sections = vec![BookItem::Chapter(chapter)];
+        for preprocessor in &self.preprocessors {
+            let should_run = || preprocessor_should_run(&**preprocessor, &RENDERER, &self.config);
+            if !block_n_yield(should_run).await {
+                continue;
+            }
+            debug!(preprocessor = preprocessor.name(), "Running.",);
+            // NOTE: Earlier preprocessors may have changed the content.
+            let maybe_split = match preprocessor.name() == LINKS_PREPROCESSOR_NAME {
+                true => split_at_directives(&mut book.sections),
+                false => None,
+            };
+            let run = || preprocessor.run(&preprocess_ctx.lock().unwrap(), book);
+            book = block_n_yield(run).await?;
+            if let Some((content, directives)) = maybe_split {
+                let pieces = mem::take(&mut book.sections);
+                let (sections, expansions) = join_pieces(pieces)?;
+                book.sections = sections;
+                maybe_expansions = Some(LinkExpansions {
+                    content,
+                    directives,
+                    expansions,
+                });
+            }
+        }
+        match book.sections.pop() {
+            Some(BookItem::Chapter(chapter)) if book.sections.is_empty() => {
+                Ok((chapter, maybe_expansions))
+            }
+            sections => bail!("The chapter preprocessed to unexpected {sections:?}."),
+        }
+    }
+
+    fn preprocess_ctx(&self) -> PreprocessorContext {
+        PreprocessorContext {
+            root: self.root.clone(),
+            config: self.config.clone(),
+            renderer: RENDERER.name().to_string(),
+            mdbook_version: MDBOOK_VERSION.to_string(),
+            chapter_titles: RefCell::new(HashMap::new()),
+            __non_exhaustive: (),
+        }
+    }
+}
+
+/// What the links preprocessor expanded in a chapter.
+#[derive(Clone, Debug)]
+pub struct LinkExpansions {
+    /// The content the links preprocessor ran on.
+    pub content: String,
+    /// Ranges of the directives in `content`.
+    pub directives: Vec<Range<usize>>,
+    /// What each directive expanded to.
+    pub expansions: Vec<String>,
 }
+
+/// Split the only chapter in `sections` at the directives in its content
+/// into chapters of the text between them and the directives.
+/// Returns the content split and the directives' ranges in it,
+/// or [`None`] if there is nothing to split.
+fn split_at_directives(sections: &mut Vec<BookItem>) -> Option<(String, Vec<Range<usize>>)> {
+    let [BookItem::Chapter(chapter)] = sections.as_slice() else {
+        return None;
+    };
+    let directives = link_directives(&chapter.content);
+    if directives.is_empty() {
+        return None;
+    }
+    let boundaries = iter::once(0)
+        .chain(directives.iter().flat_map(|range| [range.start, range.end]))
+        .chain(iter::once(chapter.content.len()))
+        .collect::<Vec<_>>();
+    let pieces = boundaries
+        .windows(2)
+        .map(|range| {
+            let content = chapter.content[range[0]..range[1]].into();
+            BookItem::Chapter(Chapter {
+                content,
+                ..chapter.clone()
+            })
+        })
+        .collect();
+    let content = chapter.content.clone();
+    *sections = pieces;
+    Some((content, directives))
+}
+
+/// Join the chapters from [`split_at_directives`] back into one,
+/// and return the expansions of the directives, every other piece.
+fn join_pieces(pieces: Vec<BookItem>) -> Result<(Vec<BookItem>, Vec<String>)> {
+    let mut pieces = pieces.into_iter().map(|item| match item {
+        BookItem::Chapter(chapter) => Ok(chapter),
+        item => bail!("A chapter piece preprocessed to unexpected {item:?}."),
+    });
+    let Some(mut chapter) = pieces.next().transpose()? else {
+        bail!("The chapter pieces preprocessed to nothing.");
+    };
+    let mut expansions = Vec::new();
+    for (index, piece) in pieces.enumerate() {
+        let piece = piece?;
+        chapter.content.push_str(&piece.content);
+        if index % 2 == 0 {
+            expansions.push(piece.content);
+        }
+    }
+    Ok((vec![BookItem::Chapter(chapter)], expansions))
+}
+
 impl From<MDBook> for MDBookCore {
     fn from(value: MDBook) -> Self {
         Self {
@@ -425,3 +620,89 @@ impl From<MDBook> for MDBookCore {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mdbook::preprocess::LinkPreprocessor;
+
+    use super::*;
+
+    /// Prepends a line to each chapter, like preprocessors ordered before
+    /// `links` that change the content.
+    struct Prepend;
+
+    impl Preprocessor for Prepend {
+        fn name(&self) -> &str {
+            "prepend"
+        }
+
+        fn run(&self, _: &PreprocessorContext, mut book: Book) -> Result<Book> {
+            book.for_each_mut(|item| {
+                if let BookItem::Chapter(chapter) = item {
+                    chapter.content.insert_str(0, "Prepended ünïcode\n\n");
+                }
+            });
+            Ok(book)
+        }
+    }
+
+    /// A book at a temporary root with `src/part.md`.
+    fn book_with_part(
+        preprocessors: Vec<Box<dyn Preprocessor + Send + Sync + 'static>>,
+    ) -> Result<(TempDir, MDBookCore)> {
+        let root = tempdir()?;
+        std::fs::create_dir(root.path().join("src"))?;
+        std::fs::write(root.path().join("src/part.md"), "Part\n\nlines\n")?;
+        let book = MDBookCore {
+            root: root.path().into(),
+            preprocessors,
+            ..Default::default()
+        };
+        Ok((root, book))
+    }
+
+    /// The content of `chapter` preprocessed as a whole book.
+    async fn preprocess_as_book(book: &MDBookCore, chapter: Chapter) -> Result<String> {
+        let mut whole_book = Book::new();
+        whole_book.sections = vec![BookItem::Chapter(chapter)];
+        let (mut whole_book, _) = book.preprocess_book(whole_book).await?;
+        match whole_book.sections.pop() {
+            Some(BookItem::Chapter(chapter)) => Ok(chapter.content),
+            _ => bail!("No chapter."),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn preprocess_chapter_same_as_whole_book() -> Result<()> {
+        let (_root, book) = book_with_part(vec![Box::new(LinkPreprocessor::new())])?;
+        let content = "# Title\n\n{{#include part.md}}\n\n\\{{#include part.md}}\n";
+        let chapter = Chapter::new("Title", content.into(), "chapter.md", vec![]);
+        let whole_content = preprocess_as_book(&book, chapter.clone()).await?;
+
+        let (chapter, expansions) = book.preprocess_chapter(chapter).await?;
+        assert_eq!(chapter.content, whole_content);
+        let expansions = expansions.context("No expansions.")?;
+        assert_eq!(expansions.content, content);
+        assert_eq!(expansions.expansions, ["Part\n\nlines"]);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn preprocess_chapter_changed_before_links() -> Result<()> {
+        let preprocessors: Vec<Box<dyn Preprocessor + Send + Sync + 'static>> =
+            vec![Box::new(Prepend), Box::new(LinkPreprocessor::new())];
+        let (_root, book) = book_with_part(preprocessors)?;
+        let content = "# Title\n\n{{#include part.md}}\n";
+        let chapter = Chapter::new("Title", content.into(), "chapter.md", vec![]);
+        let whole_content = preprocess_as_book(&book, chapter.clone()).await?;
+
+        let (chapter, expansions) = book.preprocess_chapter(chapter).await?;
+        assert_eq!(chapter.content, whole_content);
+        assert!(chapter.content.contains("Part\n\nlines"));
+        // The expansions are of the changed content, not of the source.
+        let expansions = expansions.context("No expansions.")?;
+        assert_ne!(expansions.content, content);
+        assert_eq!(expansions.expansions, ["Part\n\nlines"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+use super::*;
+
+/// What the search index is created from:
+/// the search configuration, the build directory,
+/// and the preprocessed chapters with the latest patches.
+///
+/// NOTE: `mdbook_fork4ls` does not expose the search documents of
+/// each chapter, so the index is re-created with its `search::create_files`
+/// from the kept Markdown instead of duplicating its indexer.
+#[derive(Clone, Debug)]
+pub struct SearchIndexSource {
+    pub config: Search,
+    pub destination: PathBuf,
+    /// The non-draft chapters, in the book's order,
+    /// including those the configuration excludes so it stays valid.
+    pub chapters: Vec<SearchChapter>,
+}
+
+/// A chapter's part of the search index.
+#[derive(Clone, Debug)]
+pub struct SearchChapter {
+    /// Relative path of the chapter in the book, the `.md` file.
+    pub path: PathBuf,
+    /// Relative path of the chapter's source file, for the configuration.
+    pub source_path: Option<PathBuf>,
+    /// Names of the parent chapters and the chapter.
+    pub names: Vec<String>,
+    /// The preprocessed Markdown, shared so cloning the source is cheap.
+    pub markdown: Arc<str>,
+}
+
+impl SearchIndexSource {
+    /// Keep the chapters in the preprocessed `book` for the search index.
+    pub fn new(config: Search, destination: PathBuf, book: &Book) -> Self {
+        let chapters = book
+            .iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(
+                    chapter @ Chapter {
+                        path: Some(path), ..
+                    },
+                ) => {
+                    let mut names = chapter.parent_names.clone();
+                    names.push(chapter.name.clone());
+                    Some(SearchChapter {
+                        path: path.clone(),
+                        source_path: chapter.source_path.clone(),
+                        names,
+                        markdown: chapter.content.as_str().into(),
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+        Self {
+            config,
+            destination,
+            chapters,
+        }
+    }
+
+    /// Keep the `chapters` still in the book in their order
+    /// with their new names, and add the added ones.
+    pub fn update_toc(&mut self, chapters: &[TocChapter]) {
+        let mut old_chapters: HashMap<PathBuf, SearchChapter> = mem::take(&mut self.chapters)
+            .into_iter()
+            .map(|chapter| (chapter.path.clone(), chapter))
+            .collect();
+        for chapter in chapters {
+            let (source_path, markdown) =
+                match (old_chapters.remove(&chapter.path), &chapter.added_markdown) {
+                    (Some(old_chapter), _) => (old_chapter.source_path, old_chapter.markdown),
+                    (None, Some(markdown)) => {
+                        (Some(chapter.path.clone()), markdown.as_str().into())
+                    }
+                    (None, None) => {
+                        warn!(?chapter.path, "No Markdown to index the chapter.");
+                        continue;
+                    }
+                };
+            self.chapters.push(SearchChapter {
+                path: chapter.path.clone(),
+                source_path,
+                names: chapter.names.clone(),
+                markdown,
+            });
+        }
+    }
+
+    /// Replace the Markdown of the chapter rendered to relative HTTP `path`
+    /// with the preprocessed `markdown`.
+    /// Returns if the Markdown changed.
+    pub fn update_chapter(&mut self, path: &Path, markdown: &str) -> bool {
+        match self
+            .chapters
+            .iter_mut()
+            .find(|chapter| chapter.path.with_extension("html") == path)
+        {
+            Some(chapter) if *chapter.markdown != *markdown => {
+                chapter.markdown = markdown.into();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Create the search index and the searcher scripts in the build directory,
+    /// if the configuration copies the search files.
+    pub fn write(&self) -> Result<()> {
+        if !self.config.copy_js {
+            return Ok(());
+        }
+        let mut book = Book::new();
+        book.sections = self
+            .chapters
+            .iter()
+            .filter_map(|chapter| {
+                let (name, parent_names) = chapter.names.split_last()?;
+                let mut item = Chapter::new(
+                    name,
+                    chapter.markdown.to_string(),
+                    &chapter.path,
+                    parent_names.to_vec(),
+                );
+                item.source_path.clone_from(&chapter.source_path);
+                Some(BookItem::Chapter(item))
+            })
+            .collect();
+        search::create_files(&self.config, &self.destination, &book)
+    }
+}
+
+/// Re-create the search index from the latest [`SearchIndexSource`] each time
+/// `source_rx` changes, and broadcast [`LivePatchMessage::SearchIndex`]
+/// to all tabs.
+///
+/// NOTE: The watch channel coalesces the patches made while writing,
+/// so writes never pile up or race each other.
+pub async fn reindex_search_continuously(
+    mut source_rx: watch::Receiver<Option<SearchIndexSource>>,
+    broadcast_tx: broadcast::Sender<(Option<PathBuf>, LivePatchMessage)>,
+) {
+    while source_rx.changed().await.is_ok() {
+        let Some(source) = source_rx.borrow_and_update().clone() else {
+            continue;
+        };
+        debug!(?source.destination, "Re-creating the search index.");
+        match spawn_blocking(move || source.write()).await {
+            Ok(Ok(())) => {
+                // No receivers is fine.
+                _ = broadcast_tx.send((None, LivePatchMessage::SearchIndex));
+            }
+            Ok(Err(err)) => error!(?err, "Re-creating the search index."),
+            Err(err) => error!(?err, "Joining the search index task."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mdbook::config::SearchChapterSettings;
+
+    use super::*;
+
+    fn book() -> Book {
+        let intro = Chapter::new("Intro", "# Intro\n\nHello.\n".into(), "intro.md", vec![]);
+        let mut chapter = Chapter::new(
+            "Chapter",
+            "# Chapter\n\nText[^1] <b>bold</b>\n\n## Section\n\nMore.\n\n[^1]: Note.\n".into(),
+            "dir/chapter.md",
+            vec![],
+        );
+        chapter.sub_items = vec![BookItem::Chapter(Chapter::new(
+            "Sub",
+            "No heading.\n".into(),
+            "dir/sub.md",
+            vec!["Chapter".into()],
+        ))];
+        let mut book = Book::new();
+        book.sections = vec![
+            BookItem::Chapter(intro),
+            BookItem::Separator,
+            BookItem::Chapter(chapter),
+            BookItem::Chapter(Chapter::new_draft("Draft", vec![])),
+        ];
+        book
+    }
+
+    fn search_config() -> Search {
+        let mut config = Search::default();
+        let disabled = SearchChapterSettings {
+            enable: Some(false),
+        };
+        config.chapter.insert("intro.md".into(), disabled);
+        config
+    }
+
+    #[test]
+    fn write_same_index_as_mdbook() -> Result<()> {
+        let (expected, actual) = (tempdir()?, tempdir()?);
+        search::create_files(&search_config(), expected.path(), &book())?;
+        let source = SearchIndexSource::new(search_config(), actual.path().into(), &book());
+        source.write()?;
+        for file in ["searchindex.json", "searchindex.js", "searcher.js"] {
+            let read = |dir: &TempDir| std::fs::read_to_string(dir.path().join(file));
+            assert_eq!(read(&actual)?, read(&expected)?, "{file}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn update_only_the_patched_chapter() -> Result<()> {
+        let (expected, actual) = (tempdir()?, tempdir()?);
+        let mut source = SearchIndexSource::new(search_config(), actual.path().into(), &book());
+        let sub_markdown = source.chapters[2].markdown.clone();
+        assert!(source.update_chapter(Path::new("dir/chapter.html"), "# Changed\n"));
+        assert!(!source.update_chapter(Path::new("dir/chapter.html"), "# Changed\n"));
+        assert!(!source.update_chapter(Path::new("missing.html"), "# Changed\n"));
+        assert!(Arc::ptr_eq(&source.chapters[2].markdown, &sub_markdown));
+        source.write()?;
+        let mut new_book = book();
+        new_book.for_each_mut(|item| match item {
+            BookItem::Chapter(chapter) if chapter.name == "Chapter" => {
+                chapter.content = "# Changed\n".into()
+            }
+            _ => {}
+        });
+        search::create_files(&search_config(), expected.path(), &new_book)?;
+        let read = |dir: &TempDir| std::fs::read_to_string(dir.path().join("searchindex.json"));
+        assert_eq!(read(&actual)?, read(&expected)?);
+        Ok(())
+    }
+
+    #[test]
+    fn update_toc_same_as_mdbook() -> Result<()> {
+        let (expected, actual) = (tempdir()?, tempdir()?);
+        let new_content = "# New\n\nAdded.\n";
+        let mut new_book = Book::new();
+        new_book.sections = vec![
+            BookItem::Chapter(Chapter::new("New", new_content.into(), "new.md", vec![])),
+            BookItem::Chapter(Chapter::new(
+                "Renamed",
+                "No heading.\n".into(),
+                "dir/sub.md",
+                vec![],
+            )),
+            BookItem::Chapter(Chapter::new(
+                "Intro",
+                "# Intro\n\nHello.\n".into(),
+                "intro.md",
+                vec![],
+            )),
+        ];
+        search::create_files(&search_config(), expected.path(), &new_book)?;
+        let mut source = SearchIndexSource::new(search_config(), actual.path().into(), &book());
+        let toc_chapter = |path: &str, name: &str, added_markdown: Option<&str>| TocChapter {
+            path: path.into(),
+            names: vec![name.into()],
+            added_markdown: added_markdown.map(Into::into),
+        };
+        source.update_toc(&[
+            toc_chapter("new.md", "New", Some(new_content)),
+            toc_chapter("dir/sub.md", "Renamed", None),
+            toc_chapter("intro.md", "Intro", None),
+        ]);
+        source.write()?;
+        let read = |dir: &TempDir| std::fs::read_to_string(dir.path().join("searchindex.json"));
+        assert_eq!(read(&actual)?, read(&expected)?);
+        Ok(())
+    }
+}
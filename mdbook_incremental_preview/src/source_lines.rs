@@ -0,0 +1,288 @@
+use super::*;
+
+/// Attribute holding the zero-based source line of a rendered block.
+pub const SOURCE_LINE_ATTRIBUTE: &str = "data-source-line";
+
+/// Comment marking the source line of the next rendered block,
+/// before [`annotate_source_lines`] turns it into an attribute.
+static SOURCE_LINE_MARKER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"<!--data-source-line=(\d+)-->(?:\s*(<[a-zA-Z][a-zA-Z0-9-]*))?").unwrap()
+});
+
+/// Start tag of a block annotated by [`annotate_source_lines`].
+static ANNOTATED_TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(<[a-zA-Z][a-zA-Z0-9-]*) data-source-line="(\d+)""#).unwrap());
+
+/// Directives the links preprocessor replaces, and escaped ones it keeps;
+/// same as its regex.
+pub static LINK_DIRECTIVE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?x)              # insignificant whitespace mode
+        \\\{\{\#.*\}\}      # match escaped link
+        |                   # or
+        \{\{\s*             # link opening parens and whitespace
+        \#([a-zA-Z0-9_]+)   # link type
+        \s+                 # separating whitespace
+        ([^}]+)             # link target path and space separated properties
+        \}\}                # link closing parens",
+    )
+    .unwrap()
+});
+
+/// Insert a comment marking the source line before
+/// each top-level block in `markdown` for [`annotate_source_lines`].
+///
+/// `source_lines` are the source lines of the lines of `markdown`,
+/// e.g., from [`expanded_source_lines`]; if empty, the lines are kept.
+pub fn mark_source_lines(
+    markdown: &str,
+    source_lines: &[usize],
+    smart_punctuation: bool,
+) -> String {
+    let line_starts: Vec<usize> = iter::once(0)
+        .chain(markdown.match_indices('\n').map(|(index, _)| index + 1))
+        .collect();
+    let mut marked = String::with_capacity(markdown.len() + line_starts.len() * 8);
+    let (mut copied_until, mut depth) = (0, 0usize);
+    for (event, range) in utils::new_cmark_parser(markdown, smart_punctuation).into_offset_iter() {
+        let block_start = match event {
+            // NOTE: mdBook moves footnote definitions to the end.
+            Event::Start(Tag::FootnoteDefinition(_)) => {
+                depth += 1;
+                None
+            }
+            Event::Start(_) => {
+                depth += 1;
+                (depth == 1).then_some(range.start)
+            }
+            Event::End(_) => {
+                depth = depth.saturating_sub(1);
+                None
+            }
+            Event::Rule => (depth == 0).then_some(range.start),
+            _ => None,
+        };
+        let Some(block_start) = block_start else {
+            continue;
+        };
+        let line = line_starts.partition_point(|start| *start <= block_start) - 1;
+        let line_start = line_starts[line];
+        if line_start < copied_until {
+            continue;
+        }
+        let source_line = source_lines
+            .get(line)
+            .or(source_lines.last())
+            .map_or(line, |source_line| *source_line);
+        marked.push_str(&markdown[copied_until..line_start]);
+        // NOTE: A comment line is an HTML block that interrupts paragraphs,
+        // so it does not change the other blocks.
+        marked.push_str(&format!("<!--{SOURCE_LINE_ATTRIBUTE}={source_line}-->\n"));
+        copied_until = line_start;
+    }
+    marked.push_str(&markdown[copied_until..]);
+    marked
+}
+
+/// Turn the comments from [`mark_source_lines`] in the rendered `html` into
+/// the [`SOURCE_LINE_ATTRIBUTE`] of the elements right after them;
+/// after post-processing so the header links still apply.
+pub fn annotate_source_lines(html: &str) -> String {
+    SOURCE_LINE_MARKER
+        .replace_all(html, |captures: &Captures| match captures.get(2) {
+            Some(tag) => format!(
+                r#"{} {SOURCE_LINE_ATTRIBUTE}="{}""#,
+                tag.as_str(),
+                &captures[1]
+            ),
+            None => String::new(),
+        })
+        .into_owned()
+}
+
+/// Source line of each line of `content` after replacing
+/// the `directives` ranges with the `expansions`.
+pub fn expanded_source_lines(
+    content: &str,
+    directives: &[Range<usize>],
+    expansions: &[String],
+) -> Vec<usize> {
+    let mut source_lines = vec![0];
+    let (mut copied_until, mut source_line) = (0, 0);
+    for (directive, expansion) in directives.iter().zip(expansions) {
+        for _ in content[copied_until..directive.start].matches('\n') {
+            source_line += 1;
+            source_lines.push(source_line);
+        }
+        source_lines.extend(expansion.matches('\n').map(|_| source_line));
+        source_line += content[directive.clone()].matches('\n').count();
+        copied_until = directive.end;
+    }
+    for _ in content[copied_until..].matches('\n') {
+        source_line += 1;
+        source_lines.push(source_line);
+    }
+    source_lines
+}
+
+/// Ranges of the unescaped directives in `content` that
+/// the links preprocessor replaces.
+pub fn link_directives(content: &str) -> Vec<Range<usize>> {
+    LINK_DIRECTIVE
+        .captures_iter(content)
+        .filter(|captures| captures.get(1).is_some())
+        .map(|captures| captures.get(0).unwrap().range())
+        .collect()
+}
+
+/// Source lines of the top-level elements in the annotated `new_html` that
+/// are not in the annotated `old_html`, ignoring the source lines.
+pub fn changed_source_lines(old_html: &str, new_html: &str) -> Vec<usize> {
+    let mut old_blocks: HashMap<String, usize> = HashMap::new();
+    for (_, block) in annotated_blocks(old_html) {
+        *old_blocks.entry(block).or_default() += 1;
+    }
+    annotated_blocks(new_html)
+        .into_iter()
+        .filter_map(|(line, block)| match old_blocks.get_mut(&block) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                None
+            }
+            _ => Some(line),
+        })
+        .collect()
+}
+
+/// Source line and HTML without the source line of each annotated block in
+/// `html`, up to the next annotated block.
+fn annotated_blocks(html: &str) -> Vec<(usize, String)> {
+    let tags: Vec<_> = ANNOTATED_TAG.captures_iter(html).collect();
+    let ends = tags
+        .iter()
+        .skip(1)
+        .map(|captures| captures.get(0).unwrap().start())
+        .chain(iter::once(html.len()));
+    tags.iter()
+        .zip(ends)
+        .filter_map(|(captures, end)| {
+            let line = captures[2].parse().ok()?;
+            let rest = &html[captures.get(0).unwrap().end()..end];
+            Some((line, format!("{}{rest}", &captures[1])))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(markdown: &str, source_lines: &[usize]) -> String {
+        let marked = mark_source_lines(markdown, source_lines, false);
+        annotate_source_lines(&utils::render_markdown(&marked, false))
+    }
+
+    fn lines_of(html: &str) -> Vec<(String, usize)> {
+        ANNOTATED_TAG
+            .captures_iter(html)
+            .map(|captures| (captures[1][1..].to_owned(), captures[2].parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn annotate_blocks_with_source_lines() {
+        let markdown =
+            "# Title\n\nSome *text*\nwrapping.\n\n- a\n- b\n\n---\n\n| a |\n|---|\n| 1 |\n";
+        let html = render(markdown, &[]);
+        let expected = [("h1", 0), ("p", 2), ("ul", 5), ("hr", 8), ("div", 10)];
+        let expected: Vec<_> = expected.map(|(tag, line)| (tag.to_owned(), line)).into();
+        assert_eq!(lines_of(&html), expected);
+        let attributes = Regex::new(r#" data-source-line="\d+""#).unwrap();
+        assert_eq!(
+            attributes.replace_all(&html, ""),
+            utils::render_markdown(markdown, false)
+        );
+    }
+
+    #[test]
+    fn annotate_blocks_after_raw_html() {
+        let markdown = "\
+<details>
+<summary>More</summary>
+
+Inside *details*.
+
+</details>
+
+Text[^1]
+
+```rust
+let a = 1;
+```
+
+- item
+
+[^1]: Note.
+";
+        let html = render(markdown, &[]);
+        let expected = [("details", 0), ("p", 3), ("p", 7), ("pre", 9), ("ul", 13)];
+        let expected: Vec<_> = expected.map(|(tag, line)| (tag.to_owned(), line)).into();
+        assert_eq!(lines_of(&html), expected);
+        assert!(html.contains("</details>"));
+        assert!(html.contains("<p>Note."));
+    }
+
+    #[test]
+    fn annotate_blocks_interrupting_others() {
+        let markdown = "Text\n# Heading\n> Quote\n```\ncode\n```\n- item\n# Heading\n";
+        let html = render(markdown, &[]);
+        let expected = [
+            ("p", 0),
+            ("h1", 1),
+            ("blockquote", 2),
+            ("pre", 3),
+            ("ul", 6),
+            ("h1", 7),
+        ];
+        let expected: Vec<_> = expected.map(|(tag, line)| (tag.to_owned(), line)).into();
+        assert_eq!(lines_of(&html), expected);
+    }
+
+    #[test]
+    fn annotate_blocks_with_mapped_lines() {
+        let content = "# Title\n\n{{#include a.md}}\n\nAfter\n";
+        let directives = link_directives(content);
+        assert_eq!(directives, vec![9..26]);
+        let source_lines =
+            expanded_source_lines(content, &directives, &["Included\n\nlines\n".into()]);
+        assert_eq!(source_lines, [0, 1, 2, 2, 2, 2, 3, 4, 5]);
+        let markdown = "# Title\n\nIncluded\n\nlines\n\n\nAfter\n";
+        let expected = [("h1", 0), ("p", 2), ("p", 2), ("p", 4)];
+        let expected: Vec<_> = expected.map(|(tag, line)| (tag.to_owned(), line)).into();
+        assert_eq!(lines_of(&render(markdown, &source_lines)), expected);
+    }
+
+    #[test]
+    fn link_directives_skip_escaped() {
+        let content = "\\{{#include a.md}}\n{{ #title T }} and {{#include b.md:1:2}}\n";
+        assert_eq!(link_directives(content), [19..33, 38..59]);
+    }
+
+    #[test]
+    fn changed_source_lines_ignore_shifted_lines() {
+        let old_html = render("# Title\n\nSame\n\nOld\n", &[]);
+        let new_html = render("# Title\n\nAdded\n\nSame\n\nNew\n\n---\n", &[]);
+        assert_eq!(changed_source_lines(&old_html, &new_html), [2, 6, 8]);
+        assert_eq!(
+            changed_source_lines(&new_html, &new_html),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn changed_source_lines_count_duplicates() {
+        let old_html = render("Same\n\nOther\n", &[]);
+        let new_html = render("Same\n\nSame\n\nOther\n", &[]);
+        assert_eq!(changed_source_lines(&old_html, &new_html), [2]);
+    }
+}
@@ -0,0 +1,360 @@
+use super::*;
+
+/// Where to read the changed `SUMMARY.md` from.
+#[derive(Clone, Debug)]
+pub enum SummarySource {
+    /// The saved file, creating the missing chapters if configured.
+    Disk,
+    /// The unsaved content in the editor.
+    Editor(String),
+}
+
+/// Result of patching the book for a changed `SUMMARY.md`.
+#[derive(Debug)]
+pub struct SummaryData {
+    /// Context of each chapter now in the book, by absolute path.
+    pub path2ctxs: HashMap<Arc<Path>, CtxCore>,
+    /// Relative HTTP paths of the chapters removed from the book.
+    pub removed: Vec<PathBuf>,
+    /// Relative HTTP paths of the chapters whose pages were re-rendered.
+    pub rerendered: Vec<PathBuf>,
+    /// The rendered chapters now in the book, in order.
+    pub chapters: Vec<TocChapter>,
+    /// Template data for `print.html`, without the content.
+    pub print_data: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A rendered chapter in the table of contents.
+#[derive(Clone, Debug)]
+pub struct TocChapter {
+    /// Relative path of the chapter in the book, the `.md` file.
+    pub path: PathBuf,
+    /// Names of the parent chapters and the chapter.
+    pub names: Vec<String>,
+    /// The preprocessed Markdown if the chapter was just added to the book.
+    pub added_markdown: Option<String>,
+}
+
+/// How patching the book for a changed `SUMMARY.md` went.
+#[derive(Debug)]
+pub enum SummaryOutcome {
+    Patched(Box<SummaryData>),
+    /// The book needs a full rebuild instead.
+    Rebuild,
+    /// The unsaved `SUMMARY.md` does not load, with the error message.
+    Failed(String),
+}
+
+/// Patch the built book for the changed `SUMMARY.md` in `src_dir`:
+/// re-render the table of contents, render the added chapters and
+/// those whose names or previous or next chapters changed,
+/// and delete the removed ones, keeping the other pages as they are.
+///
+/// - `path2ctxs` are of the chapters built so far.
+/// - `index_path` is the relative path of the current index chapter.
+///
+/// Returns [`None`] if the book needs a full rebuild instead,
+/// because the index chapter changed.
+pub async fn try_patch_summary(
+    book: &MDBookCore,
+    source: SummarySource,
+    src_dir: &Path,
+    build_dir: &Path,
+    path2ctxs: &HashMap<Arc<Path>, CtxCore>,
+    index_path: Option<&Path>,
+) -> Result<Option<SummaryData>> {
+    let new_book = match source {
+        SummarySource::Disk => block_n_yield(|| load_book(src_dir, &book.config.build)).await?,
+        SummarySource::Editor(content) => {
+            block_n_yield(|| load_book_from_summary(src_dir, &content)).await?
+        }
+    };
+    let ctx = RenderContext::new(book.root.clone(), new_book, book.config.clone(), build_dir);
+    let chapters: Vec<(&Chapter, &PathBuf)> = ctx
+        .book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(
+                chapter @ Chapter {
+                    source_path: Some(source_path),
+                    ..
+                },
+            ) => Some((chapter, source_path)),
+            _ => None,
+        })
+        .collect();
+    let new_index_path = chapters
+        .first()
+        .map(|(_, source_path)| source_path.as_path());
+    if new_index_path != index_path {
+        info!(?index_path, ?new_index_path, "The index chapter changed.");
+        return Ok(None);
+    }
+
+    let (html_config, _, theme, handlebars) =
+        block_n_yield(|| html_config_n_theme_dir_n_theme_n_handlebars(&ctx)).await?;
+    let data = block_n_yield(|| make_data(&ctx.root, &ctx.book, &ctx.config, &html_config, &theme))
+        .await?;
+    // NOTE: The full render renders the TOC in the context of
+    // the print page, at the root, after rendering the chapters.
+    let mut print_data = data.clone();
+    RENDERER.configure_print_version(&mut print_data, "");
+    if let Some(ref title) = ctx.config.book.title {
+        print_data.insert("title".to_owned(), json!(title));
+    }
+    block_n_yield(|| render_toc(&handlebars, &mut print_data, build_dir)).await?;
+    print_data.remove("content");
+
+    let old_order = order_by_position(path2ctxs);
+    let old_neighbors = neighbors(&old_order);
+    let new_order: Vec<Arc<Path>> = chapters
+        .iter()
+        .map(|(_, source_path)| src_dir.join(source_path).into())
+        .collect();
+    let new_neighbors = neighbors(&new_order);
+
+    let mut new_path2ctxs = HashMap::with_capacity(chapters.len());
+    let mut rerender_book = Book::new();
+    for (position, ((chapter, _), path)) in chapters.iter().zip(&new_order).enumerate() {
+        let chapter_ctx = CtxCore {
+            chapter_name: chapter.name.as_str().into(),
+            len_content: chapter.content.len(),
+            position,
+        };
+        let unchanged = path2ctxs.get(path).is_some_and(|ctx| {
+            ctx.chapter_name == chapter_ctx.chapter_name
+                && old_neighbors.get(path.as_ref()) == new_neighbors.get(path.as_ref())
+        });
+        if !unchanged {
+            // NOTE: Render the sub-chapters separately.
+            let chapter = Chapter {
+                sub_items: Vec::new(),
+                ..(*chapter).clone()
+            };
+            rerender_book.push_item(chapter);
+        }
+        new_path2ctxs.insert(path.clone(), chapter_ctx);
+    }
+
+    debug!(
+        n_rerendered = rerender_book.sections.len(),
+        "Rendering added and changed chapters."
+    );
+    let (rerender_book, chapter_titles) = match rerender_book.sections.is_empty() {
+        true => (rerender_book, HashMap::new()),
+        false => {
+            let (rerender_book, preprocess_ctx) = book.preprocess_book(rerender_book).await?;
+            (rerender_book, preprocess_ctx.chapter_titles.into_inner())
+        }
+    };
+    let mut rerendered = Vec::with_capacity(rerender_book.sections.len());
+    let mut added_markdowns = HashMap::new();
+    for item in rerender_book.iter() {
+        let BookItem::Chapter(Chapter {
+            content,
+            path: Some(path),
+            source_path: Some(source_path),
+            ..
+        }) = item
+        else {
+            continue;
+        };
+        let item_ctx = RenderItemContext {
+            handlebars: &handlebars,
+            destination: build_dir.to_path_buf(),
+            data: data.clone(),
+            is_index: Some(source_path.as_path()) == index_path,
+            book_config: ctx.config.book.clone(),
+            html_config: html_config.clone(),
+            edition: ctx.config.rust.edition,
+            chapter_titles: &chapter_titles,
+        };
+        block_n_yield(|| RENDERER.render_item(item, item_ctx, &mut String::new())).await?;
+        rerendered.push(path.with_extension("html"));
+        if !path2ctxs.contains_key(src_dir.join(source_path).as_path()) {
+            added_markdowns.insert(path.clone(), content.clone());
+        }
+    }
+
+    let toc_chapters = chapters
+        .iter()
+        .filter_map(|(chapter, _)| {
+            let path = chapter.path.clone()?;
+            let mut names = chapter.parent_names.clone();
+            names.push(chapter.name.clone());
+            let added_markdown = added_markdowns.remove(&path);
+            Some(TocChapter {
+                path,
+                names,
+                added_markdown,
+            })
+        })
+        .collect();
+
+    let mut removed = Vec::new();
+    for path in path2ctxs.keys() {
+        if new_path2ctxs.contains_key(path) {
+            continue;
+        }
+        let http_path = path.strip_prefix(src_dir)?.with_extension("html");
+        if let Err(err) = fs::remove_file(build_dir.join(&http_path)).await {
+            debug!(?err, ?http_path, "Removing the page of a removed chapter.");
+        }
+        removed.push(http_path);
+    }
+    info!(
+        n_chapters = new_path2ctxs.len(),
+        ?removed,
+        ?rerendered,
+        "Patched the book for the summary."
+    );
+    Ok(Some(SummaryData {
+        path2ctxs: new_path2ctxs,
+        removed,
+        rerendered,
+        chapters: toc_chapters,
+        print_data,
+    }))
+}
+
+/// Patch the built book for the changed `SUMMARY.md` from `source`.
+/// Falls back to a full rebuild if that fails or
+/// the index chapter changed for the saved file.
+#[allow(clippy::too_many_arguments)]
+pub async fn patch_summary(
+    patch_id: u64,
+    source: SummarySource,
+    book: Arc<MDBookCore>,
+    src_dir: Arc<Path>,
+    build_dir: Arc<Path>,
+    path2ctxs: HashMap<Arc<Path>, CtxCore>,
+    index_path: Option<PathBuf>,
+    env: ActorRef<Rebuilder>,
+) {
+    let from_editor = matches!(source, SummarySource::Editor(_));
+    let task = try_patch_summary(
+        &book,
+        source,
+        &src_dir,
+        &build_dir,
+        &path2ctxs,
+        index_path.as_deref(),
+    );
+    let outcome = match task.await {
+        Ok(Some(data)) => SummaryOutcome::Patched(Box::new(data)),
+        // NOTE: The full rebuild would load the saved file instead.
+        Ok(None) if from_editor => SummaryOutcome::Failed(
+            "The index chapter changed. Save SUMMARY.md to rebuild the book.".into(),
+        ),
+        Ok(None) => SummaryOutcome::Rebuild,
+        Err(err) if from_editor => {
+            info!(?err, "Patching the summary from the editor.");
+            SummaryOutcome::Failed(format!("{err:#}"))
+        }
+        Err(err) => {
+            // NOTE: The full rebuild reports the error.
+            info!(
+                ?err,
+                "Patching the summary. Falling back to a full rebuild."
+            );
+            SummaryOutcome::Rebuild
+        }
+    };
+    let msg = RebuildInfo::NewSummary { patch_id, outcome };
+    env.cast(msg).await.drop_result();
+}
+
+/// The paths in `path2ctxs` in the order of their chapters.
+fn order_by_position(path2ctxs: &HashMap<Arc<Path>, CtxCore>) -> Vec<Arc<Path>> {
+    let mut paths: Vec<_> = path2ctxs.iter().collect();
+    paths.sort_unstable_by_key(|(_, ctx)| ctx.position);
+    paths.into_iter().map(|(path, _)| path.clone()).collect()
+}
+
+/// The previous and next chapter of each chapter in `order`.
+fn neighbors(order: &[Arc<Path>]) -> HashMap<&Path, (Option<&Path>, Option<&Path>)> {
+    (0..order.len())
+        .map(|index| {
+            let previous = index.checked_sub(1).map(|index| order[index].as_ref());
+            let next = order.get(index + 1).map(AsRef::as_ref);
+            (order[index].as_ref(), (previous, next))
+        })
+        .collect()
+}
+
+// NOTE: Below is adapted from
+// <https://github.com/rust-lang/mdBook/blob/3bdcc0a5a6f3c85dd751350774261dbc357b02bd/src/book/book.rs>.
+
+/// Load the book in `src_dir` with the unsaved `SUMMARY.md` `content`,
+/// without creating the missing chapters.
+fn load_book_from_summary(src_dir: &Path, content: &str) -> Result<Book> {
+    let summary_md = src_dir.join("SUMMARY.md");
+    let summary = parse_summary(content)
+        .with_context(|| format!("Summary parsing failed for file={summary_md:?}"))?;
+
+    let prefix = summary.prefix_chapters.iter();
+    let numbered = summary.numbered_chapters.iter();
+    let suffix = summary.suffix_chapters.iter();
+
+    let mut book = Book::new();
+    for summary_item in prefix.chain(numbered).chain(suffix) {
+        book.push_item(load_summary_item(summary_item, src_dir, Vec::new())?);
+    }
+    Ok(book)
+}
+
+fn load_summary_item(
+    item: &SummaryItem,
+    src_dir: &Path,
+    parent_names: Vec<String>,
+) -> Result<BookItem> {
+    match item {
+        SummaryItem::Separator => Ok(BookItem::Separator),
+        SummaryItem::Link(ref link) => {
+            load_chapter(link, src_dir, parent_names).map(BookItem::Chapter)
+        }
+        SummaryItem::PartTitle(title) => Ok(BookItem::PartTitle(title.clone())),
+    }
+}
+
+fn load_chapter(link: &Link, src_dir: &Path, parent_names: Vec<String>) -> Result<Chapter> {
+    let mut ch = if let Some(ref link_location) = link.location {
+        debug!(link.name, ?link_location, "Loading chapter.");
+
+        let location = if link_location.is_absolute() {
+            link_location.clone()
+        } else {
+            src_dir.join(link_location)
+        };
+
+        let mut content = std::fs::read_to_string(&location)
+            .with_context(|| format!("Chapter file not found, {}", link_location.display()))?;
+
+        if content.as_bytes().starts_with(b"\xef\xbb\xbf") {
+            content.replace_range(..3, "");
+        }
+
+        let stripped = location
+            .strip_prefix(src_dir)
+            .context("Chapters are always inside a book")?;
+
+        Chapter::new(&link.name, content, stripped, parent_names.clone())
+    } else {
+        Chapter::new_draft(&link.name, parent_names.clone())
+    };
+
+    let mut sub_item_parents = parent_names;
+
+    ch.number = link.number.clone();
+
+    sub_item_parents.push(link.name.clone());
+    let sub_items = link
+        .nested_items
+        .iter()
+        .map(|i| load_summary_item(i, src_dir, sub_item_parents.clone()))
+        .collect::<Result<Vec<_>>>()?;
+
+    ch.sub_items = sub_items;
+
+    Ok(ch)
+}
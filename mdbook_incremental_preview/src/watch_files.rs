@@ -1,20 +1,18 @@
 use super::*;
 
-const DEBOUNCER_TIMEOUT: Duration = Duration::from_millis(20);
-
 pub fn watch_file_changes<F>(
     book_root: &Path,
     src_dir: &Path,
     theme_dir: &Path,
     book_toml: &Path,
     extra_watch_dirs: &[PathBuf],
+    debounce: Duration,
     event_handler: F,
 ) -> Debouncer<RecommendedWatcher>
 where
     F: DebounceEventHandler,
 {
-    let mut debouncer = match notify_debouncer_mini::new_debouncer(DEBOUNCER_TIMEOUT, event_handler)
-    {
+    let mut debouncer = match notify_debouncer_mini::new_debouncer(debounce, event_handler) {
         Ok(d) => d,
         Err(err) => {
             error!(?err, "Trying to watch files");
@@ -63,20 +63,24 @@ pub async fn serve(
     } = info;
 
     // Handle WebSockets for live-patching.
-    let p_ref = patch_registry_ref.clone();
+    let refs = (patch_registry_ref.clone(), rebuilder_ref.clone());
     let live_patch = warp::path(LIVE_PATCH_WEBSOCKET_PATH)
         .and(warp::path::tail())
         .and(warp::ws())
-        .and(warp::any().map(move || p_ref.clone()))
-        .map(move |tail: Tail, ws: Ws, patch_registry_ref| {
-            ws.on_upgrade(move |mut ws| async move {
-                if let Err(err) = handle_ws(tail.as_str(), &mut ws, patch_registry_ref).await {
-                    error!(?err, "Handling WebSocket");
-                }
-                ws.close().await.drop_result();
-                debug!("Closed WebSocket connection.");
-            })
-        });
+        .and(warp::any().map(move || refs.clone()))
+        .map(
+            move |tail: Tail, ws: Ws, (patch_registry_ref, rebuilder_ref)| {
+                ws.on_upgrade(move |mut ws| async move {
+                    let result =
+                        handle_ws(tail.as_str(), &mut ws, patch_registry_ref, rebuilder_ref).await;
+                    if let Err(err) = result {
+                        error!(?err, "Handling WebSocket");
+                    }
+                    ws.close().await.drop_result();
+                    debug!("Closed WebSocket connection.");
+                })
+            },
+        );
 
     let build_artifact = warp::get()
         // Check if the path has a patch.
@@ -115,29 +119,93 @@ pub async fn serve(
 }
 
 /// Handle live patching at the canonical `path` that may start with `/`,
-/// via the WebSocket `ws`.
+/// and the requests from the browser tab, via the WebSocket `ws`.
 async fn handle_ws(
     path: &str,
     ws: &mut WebSocket,
     patch_registry_ref: ActorRef<PatchRegistry>,
+    rebuilder_ref: ActorRef<Rebuilder>,
 ) -> Result<()> {
     let path = Path::new(path.trim_start_matches('/'));
     info!(?path, "WebSocket connection.");
+    let Some((last_build, last_patch)) = handshake(ws).await? else {
+        return Ok(());
+    };
 
     let response = patch_registry_ref
         .call(PatchRegistryQuery::Watch(path.to_owned()))
         .await;
-    let Ok(PatchRegistryResponse::WatchReceiver(mut watch_receiver)) = response else {
+    let Ok(PatchRegistryResponse::WatchReceivers {
+        path: resolved_path,
+        build_id,
+        patch_receiver: mut watch_receiver,
+        mut broadcast_receiver,
+    }) = response
+    else {
         bail!("Unexpected response calling PatchRegistry: {response:?}.");
     };
 
-    if !watch_receiver.borrow_and_update().is_empty() {
+    // Bring the tab up to date with what it last applied.
+    let (has_latest, applied_latest) = {
+        let latest = watch_receiver.borrow_and_update();
+        let applied_latest = matches!(
+            (&*latest, last_patch),
+            (Some(LivePatchMessage::Patch { id, .. }), Some(last_patch)) if *id == last_patch
+        );
+        (latest.is_some(), applied_latest)
+    };
+    let outdated_build = last_build.is_some_and(|last_build| last_build != build_id);
+    if outdated_build || (!has_latest && last_patch.is_some()) {
+        info!(
+            ?path,
+            "The tab shows content we no longer have. Telling it to reload."
+        );
+        ws.send(Message::text(LivePatchMessage::Reload.to_json()))
+            .await?;
+        return Ok(());
+    }
+    let welcome = LivePatchMessage::Welcome { build: build_id };
+    ws.send(Message::text(welcome.to_json())).await?;
+    if has_latest && !applied_latest {
         // Send the existing patch.
         watch_receiver.mark_changed();
     }
-    while watch_receiver.changed().await.is_ok() {
-        let patch = { watch_receiver.borrow_and_update().clone() };
-        if let Err(err) = ws.send(Message::text(patch)).await {
+    let msg = RebuildInfo::Connected(resolved_path.clone());
+    rebuilder_ref.cast(msg).await.drop_result();
+    loop {
+        let message = select! {
+            // Send patches before the scrolls to their blocks.
+            biased;
+            changed = watch_receiver.changed() => match changed {
+                Ok(()) => match watch_receiver.borrow_and_update().clone() {
+                    Some(message) => message,
+                    None => continue,
+                },
+                Err(_) => return Ok(()),
+            },
+            broadcast = broadcast_receiver.recv() => match broadcast {
+                Ok((None, message)) => message,
+                Ok((Some(broadcast_path), message)) if broadcast_path == resolved_path => message,
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            request = ws.next() => match request {
+                Some(Ok(request)) => {
+                    match LivePatchRequest::from_message(&request) {
+                        Some(LivePatchRequest::ShowSource { line }) => {
+                            let http_path = resolved_path.clone();
+                            let msg = RebuildInfo::ShowSource { http_path, line };
+                            rebuilder_ref.cast(msg).await.drop_result();
+                        }
+                        _ => debug!(?request, "Ignoring WebSocket request."),
+                    }
+                    continue;
+                }
+                Some(Err(err)) => bail!(err),
+                None => return Ok(()),
+            },
+        };
+        if let Err(err) = ws.send(Message::text(message.to_json())).await {
             info!(
                 ?err,
                 ?path,
@@ -145,9 +213,40 @@ async fn handle_ws(
             );
             return Ok(());
         }
-        debug!("Sent patch update to WebSocket at {path:?}.");
+        debug!("Sent update to WebSocket at {path:?}.");
     }
-    Ok(())
+}
+
+/// Wait for the browser tab to say hello with our [`PROTOCOL_VERSION`],
+/// and tell it to reload otherwise.
+/// Returns the IDs of the build and the patch the tab last applied,
+/// if the handshake succeeded.
+async fn handshake(ws: &mut WebSocket) -> Result<Option<(Option<u64>, Option<u64>)>> {
+    let request = match timeout(HANDSHAKE_TIMEOUT, ws.next()).await {
+        Ok(Some(request)) => Some(request?),
+        Ok(None) => return Ok(None),
+        Err(_) => None,
+    };
+    let reload = match request.as_ref().and_then(LivePatchRequest::from_message) {
+        Some(LivePatchRequest::Hello {
+            version,
+            build,
+            patch,
+        }) if version == PROTOCOL_VERSION => return Ok(Some((build, patch))),
+        Some(LivePatchRequest::Hello { version, .. }) => {
+            info!(
+                version,
+                PROTOCOL_VERSION, "Outdated page. Telling it to reload."
+            );
+            LivePatchMessage::Reload.to_json()
+        }
+        _ => {
+            info!(?request, "Page without handshake. Telling it to reload.");
+            LEGACY_RELOAD.into()
+        }
+    };
+    ws.send(Message::text(reload)).await?;
+    Ok(None)
 }
 
 async fn filter_patched_path(
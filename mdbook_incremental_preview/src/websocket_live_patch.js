@@ -2,19 +2,267 @@
     const wsProtocol = location.protocol === "https:" ? "wss" : "ws";
     const wsAddress =
         `${wsProtocol}://${location.host}/__mdbook_incremental_preview_live_patch${location.pathname}`;
-    const web_socket = new WebSocket(wsAddress);
+    // NOTE: Keep in sync with `PROTOCOL_VERSION` in `live_patch_protocol.rs`.
+    const protocolVersion = 4;
+    // IDs of the build and the patch we last applied, to resync on reconnection.
+    let lastBuild = null;
+    let lastPatch = null;
+    const minRetryDelay = 500;
+    const maxRetryDelay = 10000;
+    let retryDelay = minRetryDelay;
+    let web_socket = null;
+    const send = (request) => {
+        if (web_socket.readyState === WebSocket.OPEN) {
+            web_socket.send(JSON.stringify(request));
+        }
+    };
     // NOTE: We assume that the content is in <main> as per `index.hbs`.
     const contentElement = document.querySelector("main");
-    web_socket.onmessage = (event) => {
+    // Source line to scroll to once a patch marks the source lines.
+    let pendingLine = null;
+    const scrollToLine = (line) => {
+        const elements = contentElement.querySelectorAll("[data-source-line]");
+        // The last block starting at or before `line`.
+        let target = elements[0];
+        for (const element of elements) {
+            if (Number(element.dataset.sourceLine) > line) {
+                break;
+            }
+            target = element;
+        }
+        pendingLine = target === undefined ? line : null;
+        target?.scrollIntoView({ behavior: "smooth", block: "center" });
+    };
+    // Ctrl- or Cmd-click a block to show its source in the editor.
+    contentElement.addEventListener("click", (event) => {
+        if (!(event.ctrlKey || event.metaKey)) {
+            return;
+        }
+        const block = event.target.closest("[data-source-line]");
+        if (block === null || !contentElement.contains(block)) {
+            return;
+        }
+        event.preventDefault();
+        send({ type: "show_source", line: Number(block.dataset.sourceLine) });
+    });
+    // Patched HTML of each top-level node, without its source line,
+    // to tell which blocks changed.
+    const patchedKeys = new WeakMap();
+    const nodeKey = (node) =>
+        node.nodeType === Node.ELEMENT_NODE
+            ? node.outerHTML.replace(/^(<[a-z0-9]+) data-source-line="\d+"/i, "$1")
+            : `${node.nodeName}\n${node.nodeValue}`;
+    // Morph the content into `html`, keeping the nodes of unchanged blocks
+    // so their scroll position and state survive.
+    // Returns the new top-level elements.
+    const morph = (html) => {
+        const template = document.createElement("template");
+        template.innerHTML = html;
+        const unused = new Map();
+        for (const node of contentElement.childNodes) {
+            const key = patchedKeys.get(node) ?? nodeKey(node);
+            unused.has(key) ? unused.get(key).push(node) : unused.set(key, [node]);
+        }
+        const changed = [];
+        let reference = contentElement.firstChild;
+        for (const newNode of Array.from(template.content.childNodes)) {
+            const key = nodeKey(newNode);
+            let node = unused.get(key)?.shift();
+            if (node === undefined) {
+                node = newNode;
+                if (node.nodeType === Node.ELEMENT_NODE) {
+                    changed.push(node);
+                }
+            } else if (node.nodeType === Node.ELEMENT_NODE) {
+                // The block may have moved to another source line.
+                const line = newNode.getAttribute("data-source-line");
+                line === null
+                    ? node.removeAttribute("data-source-line")
+                    : node.setAttribute("data-source-line", line);
+            }
+            patchedKeys.set(node, key);
+            if (node === reference) {
+                reference = node.nextSibling;
+            } else {
+                contentElement.insertBefore(node, reference);
+            }
+        }
+        // Everything after the placed nodes is stale.
+        while (reference !== null) {
+            const next = reference.nextSibling;
+            reference.remove();
+            reference = next;
+        }
+        return changed;
+    };
+    // Highlight the code blocks in `element` like `book.js` does.
+    const highlight = (element) => {
+        if (!window.hljs) {
+            return;
+        }
+        for (const block of element.querySelectorAll("code")) {
+            // Don't highlight `inline code` blocks in headers.
+            if (block.parentElement.classList.contains("header")) {
+                continue;
+            }
+            if (window.ace && block.classList.contains("editable")) {
+                block.classList.remove("language-rust");
+            } else {
+                window.hljs.highlightBlock(block);
+            }
+            block.classList.add("hljs");
+        }
+    };
+    // Briefly mark a changed block.
+    const flash = (element) =>
+        element.animate(
+            [{ backgroundColor: "rgba(255, 200, 0, 0.35)" }, { backgroundColor: "transparent" }],
+            { duration: 1500, easing: "ease-out" },
+        );
+    // NOTE: `searcher.js` keeps its search index and document URLs private
+    // and loads them after this script runs, so we capture the index it loads
+    // and the URL array it keeps to update them in place;
+    // calling its `init` again would register its event listeners twice.
+    let searchIndex = null;
+    let searchDocUrls = null;
+    const loadIndex = window.elasticlunr?.Index.load;
+    const fetchOriginal = window.fetch;
+    if (loadIndex !== undefined) {
+        window.elasticlunr.Index.load = (serialized) => {
+            // Without the JSON, `searcher.js` loaded `searchindex.js`
+            // into `window.search` and keeps its URLs.
+            searchDocUrls ??= window.search?.doc_urls ?? null;
+            return (searchIndex = loadIndex.call(window.elasticlunr.Index, serialized));
+        };
+        window.fetch = async (...args) => {
+            const response = await fetchOriginal(...args);
+            if (searchDocUrls === null && String(args[0]).endsWith("searchindex.json")) {
+                const json = response.json.bind(response);
+                response.json = async () => {
+                    const config = await json();
+                    searchDocUrls = config.doc_urls;
+                    return config;
+                };
+            }
+            return response;
+        };
+    }
+    const reloadSearchIndex = async () => {
+        // Without the URLs, the new index would link results to wrong pages.
+        if (
+            searchIndex === null ||
+            !Array.isArray(searchDocUrls) ||
+            typeof path_to_root === "undefined"
+        ) {
+            return;
+        }
+        const response = await fetchOriginal(`${path_to_root}searchindex.json`, {
+            cache: "no-store",
+        });
+        const { doc_urls, index } = await response.json();
+        const newIndex = loadIndex.call(window.elasticlunr.Index, index);
+        searchDocUrls.length = 0;
+        for (const url of doc_urls) {
+            searchDocUrls.push(url);
+        }
+        Object.assign(searchIndex, newIndex);
+    };
+    // NOTE: `toc.js` fills the sidebar when its custom element connects,
+    // and defining the element again throws, so we run the new `toc.js` with
+    // a stand-in `window` to only get its element class.
+    const reloadToc = async () => {
+        if (typeof path_to_root === "undefined") {
+            return;
+        }
+        const response = await fetch(`${path_to_root}toc.js`, { cache: "no-store" });
+        let Scrollbox = null;
+        const tocWindow = { customElements: { define: (_name, element) => (Scrollbox = element) } };
+        new Function("window", await response.text())(tocWindow);
+        if (Scrollbox === null) {
+            return;
+        }
+        for (const scrollbox of document.querySelectorAll("mdbook-sidebar-scrollbox")) {
+            const scrollTop = scrollbox.scrollTop;
+            Scrollbox.prototype.connectedCallback.call(scrollbox);
+            scrollbox.scrollTop = scrollTop;
+        }
+    };
+    const errorOverlay = document.createElement("pre");
+    errorOverlay.style.cssText =
+        "position: fixed; inset: auto 0 0 0; z-index: 1000; margin: 0; padding: 1em;" +
+        "max-height: 50vh; overflow: auto; white-space: pre-wrap;" +
+        "background: #fdd; color: #600; border-top: 3px solid #c00;";
+    errorOverlay.title = "Click to dismiss.";
+    errorOverlay.onclick = () => errorOverlay.remove();
+    const applyPatch = ({ id, html, changed_lines }) => {
+        lastPatch = id;
+        errorOverlay.remove();
+        const changed = morph(html);
+        if (changed.length > 0) {
+            changed.forEach(highlight);
+            document.dispatchEvent(new Event("load"));
+        }
+        for (const line of changed_lines) {
+            contentElement
+                .querySelectorAll(`:scope > [data-source-line="${line}"]`)
+                .forEach(flash);
+        }
+        if (pendingLine !== null) {
+            scrollToLine(pendingLine);
+        }
+    };
+    const onMessage = (event) => {
+        // NOTE: The server tells pages without handshake to reload as it did
+        // before the protocol, e.g., when our hello comes too late.
+        // Keep in sync with `LEGACY_RELOAD` in `live_patch_protocol.rs`.
         if (event.data === "__RELOAD") {
             location.reload();
+            return;
         }
-        contentElement.innerHTML = event.data;
-        document.dispatchEvent(new Event("load"));
-        if (window.hljs && window.hljs.initHighlighting) {
-            // Re-highlight with highlight.js.
-            window.hljs.initHighlighting.called = false;
-            window.hljs.initHighlighting();
+        const message = JSON.parse(event.data);
+        switch (message.type) {
+            case "welcome":
+                lastBuild = message.build;
+                retryDelay = minRetryDelay;
+                break;
+            case "patch":
+                applyPatch(message);
+                break;
+            case "reload":
+                location.reload();
+                break;
+            case "error":
+                // Keep the stale content but show the error until the next patch.
+                errorOverlay.textContent = message.message;
+                document.body.appendChild(errorOverlay);
+                break;
+            case "scroll_to":
+                scrollToLine(message.line);
+                break;
+            case "search_index":
+                reloadSearchIndex().catch((error) =>
+                    console.warn("Failed to reload the search index", error),
+                );
+                break;
+            case "toc":
+                reloadToc().catch((error) =>
+                    console.warn("Failed to reload the table of contents", error),
+                );
+                break;
+            default:
+                console.warn("Unknown live patch message", message);
         }
     };
+    // Connect, and reconnect with exponential backoff when the server goes away.
+    const connect = () => {
+        web_socket = new WebSocket(wsAddress);
+        web_socket.onopen = () =>
+            send({ type: "hello", version: protocolVersion, build: lastBuild, patch: lastPatch });
+        web_socket.onmessage = onMessage;
+        web_socket.onclose = () => {
+            setTimeout(connect, retryDelay);
+            retryDelay = Math.min(retryDelay * 2, maxRetryDelay);
+        };
+    };
+    connect();
 })();
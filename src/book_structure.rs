@@ -0,0 +1,392 @@
+use super::*;
+
+/// Where the book's files are and which chapters `SUMMARY.md` lists.
+#[derive(Clone, Debug, Default)]
+pub struct BookStructure {
+    pub book_root: PathBuf,
+    pub src_dir: PathBuf,
+    pub summary_md: PathBuf,
+    /// Absolute paths of the chapter files listed in `SUMMARY.md`.
+    pub chapter_paths: HashSet<PathBuf>,
+    /// Names and absolute paths of the chapters listed in `SUMMARY.md`,
+    /// in order, drafts excluded.
+    pub chapters: Vec<(String, PathBuf)>,
+    /// Draft chapters in `SUMMARY.md`, which have no file.
+    pub drafts: Vec<SummaryEntry>,
+}
+
+impl BookStructure {
+    /// Load the structure of the book at `book_root`,
+    /// reading `SUMMARY.md` from `documents` if it is open.
+    pub fn load(book_root: &Path, documents: &Documents) -> Result<Self> {
+        let book_root = absolute_path(book_root)?;
+        let BookPaths {
+            src_dir,
+            summary_md,
+            ..
+        } = BookPaths::load(&book_root)?;
+        let summary = read_source(documents, &summary_md)
+            .with_context(|| format!("Reading {summary_md:?}"))?;
+        let chapters = parse_summary_chapters(&summary.text, &src_dir);
+        let chapter_paths = chapters.iter().map(|(_, path)| path.clone()).collect();
+        let drafts = parse_summary_entries(&summary.text)
+            .into_iter()
+            .filter(SummaryEntry::is_draft)
+            .collect();
+        Ok(Self {
+            book_root,
+            src_dir,
+            summary_md,
+            chapter_paths,
+            chapters,
+            drafts,
+        })
+    }
+}
+
+/// Names and absolute paths of the chapters listed in `SUMMARY.md`,
+/// in order, drafts excluded.
+pub fn summary_chapters(paths: &BookPaths, documents: &Documents) -> Vec<(String, PathBuf)> {
+    match read_source(documents, &paths.summary_md) {
+        Some(summary) => parse_summary_chapters(&summary.text, &paths.src_dir),
+        None => vec![],
+    }
+}
+
+/// Names and absolute paths of the chapters listed in
+/// `SUMMARY.md` source `text`, in order, drafts excluded.
+pub fn parse_summary_chapters(text: &str, src_dir: &Path) -> Vec<(String, PathBuf)> {
+    match parse_summary(text) {
+        Ok(summary) => summary_links(&summary)
+            .filter_map(|link| {
+                let location = link.location.as_ref()?;
+                Some((link.name.clone(), src_dir.join(location)))
+            })
+            .collect(),
+        // NOTE: `SUMMARY.md` may be being edited, so fall back to
+        // parsing it leniently instead of losing all its chapters.
+        Err(err) => {
+            debug!(
+                ?err,
+                "Parsing SUMMARY.md. Falling back to parsing leniently."
+            );
+            parse_summary_entries(text)
+                .into_iter()
+                .filter_map(|entry| {
+                    let (location, _) = split_link_dest(&entry.location)?;
+                    let location = decode_summary_path(location);
+                    (!location.is_empty()).then(|| (entry.name, src_dir.join(location)))
+                })
+                .collect()
+        }
+    }
+}
+
+/// Titles and absolute paths of the Markdown files in the source directory
+/// not listed in `SUMMARY.md`.
+pub fn unlisted_chapters(paths: &BookPaths, documents: &Documents) -> Vec<(String, PathBuf)> {
+    let listed = summary_chapters(paths, documents)
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect();
+    unlisted_paths(&paths.src_dir, &paths.summary_md, &listed, documents)
+        .into_iter()
+        .map(|path| {
+            let title = read_source(documents, &path)
+                .and_then(|source| parse_chapter(&source.text).headings.into_iter().next())
+                .map_or_else(String::new, |heading| heading.title);
+            (title, path)
+        })
+        .collect()
+}
+
+/// Absolute paths of the Markdown files under `src_dir` not in `listed`.
+fn unlisted_paths(
+    src_dir: &Path,
+    summary_md: &Path,
+    listed: &HashSet<PathBuf>,
+    documents: &Documents,
+) -> Vec<PathBuf> {
+    markdown_files(src_dir, documents)
+        .into_iter()
+        .filter(|path| path != summary_md && !listed.contains(path))
+        .collect()
+}
+
+/// Chapters in the book that mdBook does not render.
+#[derive(Clone, Debug, Default)]
+pub struct Unrendered {
+    /// Absolute paths of the Markdown files in the source directory
+    /// neither listed in `SUMMARY.md` nor included by listed chapters.
+    pub orphans: Vec<PathBuf>,
+    /// Draft chapters in `SUMMARY.md`, which have no file.
+    pub drafts: Vec<SummaryEntry>,
+}
+
+impl Unrendered {
+    pub fn find(book: &BookStructure, files: &SourceFiles, documents: &Documents) -> Self {
+        let mut orphans: HashSet<_> = files
+            .markdown_files
+            .iter()
+            .filter(|path| **path != book.summary_md && !book.chapter_paths.contains(*path))
+            .collect();
+        let mut to_scan: Vec<_> = book.chapter_paths.iter().collect();
+        // Files pulled in by `{{#include}}` are rendered in the including chapter.
+        while let Some(path) = to_scan.pop().filter(|_| !orphans.is_empty()) {
+            for included_path in files.includes(path, documents).iter() {
+                if let Some(included_path) = orphans.take(included_path) {
+                    to_scan.push(included_path);
+                }
+            }
+        }
+        let mut orphans: Vec<_> = orphans.into_iter().cloned().collect();
+        orphans.sort();
+        let drafts = book.drafts.clone();
+        Self { orphans, drafts }
+    }
+}
+
+/// The Markdown files of a book and what they include,
+/// which change with the files but not with `SUMMARY.md`.
+#[derive(Debug, Default)]
+pub struct SourceFiles {
+    /// Absolute paths of the Markdown files under the source directory,
+    /// including open ones not yet saved.
+    pub markdown_files: BTreeSet<PathBuf>,
+    /// Absolute paths of the files each file includes by its absolute path,
+    /// loaded as needed.
+    includes: RwLock<HashMap<PathBuf, Arc<[PathBuf]>>>,
+}
+
+impl SourceFiles {
+    pub fn load(src_dir: &Path, documents: &Documents) -> Self {
+        Self {
+            markdown_files: markdown_files(src_dir, documents),
+            includes: RwLock::default(),
+        }
+    }
+
+    /// Cached absolute paths of the files the file at `path` includes,
+    /// loading them if not cached.
+    pub fn includes(&self, path: &Path, documents: &Documents) -> Arc<[PathBuf]> {
+        if let Some(includes) = self.includes.read().unwrap().get(path) {
+            return includes.clone();
+        }
+        let includes: Arc<[PathBuf]> = match read_source(documents, path) {
+            Some(source) => parse_includes(&source.text)
+                .into_iter()
+                .map(|include| resolve_relative(path, &include.path))
+                .collect(),
+            None => Arc::new([]),
+        };
+        self.includes
+            .write()
+            .unwrap()
+            .insert(path.into(), includes.clone());
+        includes
+    }
+
+    /// Forget the cached includes of the file at `path`,
+    /// e.g., because it is being edited.
+    pub fn invalidate_includes(&self, path: &Path) {
+        self.includes.write().unwrap().remove(path);
+    }
+}
+
+/// All chapter links in `summary`, depth-first.
+pub fn summary_links(summary: &Summary) -> impl Iterator<Item = &Link> {
+    let mut stack: Vec<&SummaryItem> = summary
+        .prefix_chapters
+        .iter()
+        .chain(&summary.numbered_chapters)
+        .chain(&summary.suffix_chapters)
+        .rev()
+        .collect();
+    iter::from_fn(move || loop {
+        match stack.pop()? {
+            SummaryItem::Link(link) => {
+                stack.extend(link.nested_items.iter().rev());
+                return Some(link);
+            }
+            SummaryItem::Separator | SummaryItem::PartTitle(_) => {}
+        }
+    })
+}
+
+/// Absolute paths of the Markdown files under `src_dir`,
+/// including open ones not yet saved.
+pub fn markdown_files(src_dir: &Path, documents: &Documents) -> BTreeSet<PathBuf> {
+    let is_markdown = |path: &Path| path.extension().is_some_and(|extension| extension == "md");
+    let mut files: BTreeSet<_> = documents
+        .keys()
+        .filter(|path| path.starts_with(src_dir) && is_markdown(path))
+        .cloned()
+        .collect();
+    let walker = WalkBuilder::new(src_dir).standard_filters(false).build();
+    files.extend(
+        walker
+            .flatten()
+            .map(|entry| entry.into_path())
+            .filter(|path| is_markdown(path) && !path.is_dir()),
+    );
+    files
+}
+
+/// `path` made absolute against the current directory if it is relative.
+pub fn absolute_path(path: &Path) -> Result<PathBuf> {
+    Ok(match path.is_absolute() {
+        true => path.to_owned(),
+        false => env::current_dir()?.join(path),
+    })
+}
+
+/// Resolve the relative link `path` written in the file at `base_file`,
+/// normalizing `.` and `..` components.
+pub fn resolve_relative(base_file: &Path, path: &str) -> PathBuf {
+    let mut resolved = base_file.parent().unwrap_or(Path::new("")).to_owned();
+    for component in Path::new(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => _ = resolved.pop(),
+            component => resolved.push(component),
+        }
+    }
+    resolved
+}
+
+/// Relative link path from the file at absolute `base_file` to
+/// absolute `path`, with `/` separators.
+pub fn relative_link_path(base_file: &Path, path: &Path) -> String {
+    let base_dir: Vec<_> = match base_file.parent() {
+        Some(base_dir) => base_dir.components().collect(),
+        None => vec![],
+    };
+    let components: Vec<_> = path.components().collect();
+    let n_common = iter::zip(&base_dir, &components)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let ups = iter::repeat_n(Cow::Borrowed(".."), base_dir.len() - n_common);
+    let downs = components[n_common..]
+        .iter()
+        .map(|component| component.as_os_str().to_string_lossy());
+    ups.chain(downs).collect::<Vec<_>>().join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_link_paths() {
+        let base = Path::new("/book/src/a/chapter.md");
+        let cases = [
+            ("/book/src/a/other.md", "other.md"),
+            ("/book/src/a/b/nested.md", "b/nested.md"),
+            ("/book/src/top.md", "../top.md"),
+            ("/book/src/c/d/deep.md", "../c/d/deep.md"),
+            ("/elsewhere/file.md", "../../../elsewhere/file.md"),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(relative_link_path(base, Path::new(path)), expected);
+        }
+    }
+
+    #[test]
+    fn relative_link_paths_resolve_back() {
+        let base = Path::new("/book/src/a/chapter.md");
+        let path = Path::new("/book/src/c/d/deep.md");
+        assert_eq!(
+            resolve_relative(base, &relative_link_path(base, path)),
+            path
+        );
+    }
+
+    #[test]
+    fn summary_chapters_fall_back_to_lenient_parsing() {
+        let src_dir = Path::new("/book/src");
+        let strict = "# Summary\n\n- [One](one.md)\n    - [Two](two.md)\n- [Draft]()\n";
+        let expected = vec![
+            ("One".to_owned(), src_dir.join("one.md")),
+            ("Two".to_owned(), src_dir.join("two.md")),
+        ];
+        assert_eq!(parse_summary_chapters(strict, src_dir), expected);
+        // mdBook rejects prefix chapters after numbered ones.
+        let broken = "# Summary\n\n- [One](one.md)\n    - [Two](two.md)\n\n[Three](three.md)\n\n- [Four](four.md)\n";
+        let chapters = parse_summary_chapters(broken, src_dir);
+        assert!(parse_summary(broken).is_err());
+        assert_eq!(chapters.len(), 4);
+        assert_eq!(chapters[3], ("Four".to_owned(), src_dir.join("four.md")));
+    }
+
+    #[test]
+    fn find_orphans_and_drafts() {
+        let summary = "# Summary\n\n- [One](one.md)\n- [Draft]()\n";
+        let documents = open_documents([
+            ("/book/src/SUMMARY.md", summary),
+            ("/book/src/one.md", "{{#include part.md}}\n"),
+            ("/book/src/part.md", "{{#include nested/deeper.md}}\n"),
+            ("/book/src/nested/deeper.md", "Deeper.\n"),
+            ("/book/src/orphan.md", "# Orphan\n"),
+            ("/book/src/code.rs", "fn main() {}\n"),
+        ]);
+        let src_dir = PathBuf::from("/book/src");
+        let book = BookStructure {
+            chapter_paths: HashSet::from([src_dir.join("one.md")]),
+            drafts: parse_summary_entries(summary)
+                .into_iter()
+                .filter(SummaryEntry::is_draft)
+                .collect(),
+            summary_md: src_dir.join("SUMMARY.md"),
+            src_dir,
+            ..Default::default()
+        };
+        let files = SourceFiles::load(&book.src_dir, &documents);
+        let Unrendered { orphans, drafts } = Unrendered::find(&book, &files, &documents);
+        assert_eq!(orphans, [PathBuf::from("/book/src/orphan.md")]);
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(drafts[0].name, "Draft");
+    }
+
+    #[test]
+    fn find_orphans_with_cached_files() {
+        let mut documents = open_documents([
+            ("/book/src/SUMMARY.md", "# Summary\n\n- [One](one.md)\n"),
+            ("/book/src/one.md", "{{#include part.md}}\n"),
+            ("/book/src/part.md", "Part.\n"),
+            ("/book/src/orphan.md", "# Orphan\n"),
+        ]);
+        let src_dir = PathBuf::from("/book/src");
+        let mut book = BookStructure {
+            chapter_paths: HashSet::from([src_dir.join("one.md")]),
+            summary_md: src_dir.join("SUMMARY.md"),
+            src_dir,
+            ..Default::default()
+        };
+        let files = SourceFiles::load(&book.src_dir, &documents);
+        let orphans = Unrendered::find(&book, &files, &documents).orphans;
+        assert_eq!(orphans, [PathBuf::from("/book/src/orphan.md")]);
+
+        // Listing another chapter in `SUMMARY.md` reuses the files.
+        book.chapter_paths
+            .insert(PathBuf::from("/book/src/orphan.md"));
+        assert!(Unrendered::find(&book, &files, &documents)
+            .orphans
+            .is_empty());
+
+        // Editing a chapter changes its includes only once they are invalidated.
+        let one = PathBuf::from("/book/src/one.md");
+        documents.get_mut(&one).unwrap().source = SourceText::new("# One\n".into());
+        book.chapter_paths.remove(Path::new("/book/src/orphan.md"));
+        let orphans = Unrendered::find(&book, &files, &documents).orphans;
+        assert_eq!(orphans, [PathBuf::from("/book/src/orphan.md")]);
+        files.invalidate_includes(&one);
+        let orphans = Unrendered::find(&book, &files, &documents).orphans;
+        assert_eq!(
+            orphans,
+            [
+                PathBuf::from("/book/src/orphan.md"),
+                PathBuf::from("/book/src/part.md"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,335 @@
+use super::*;
+
+/// Quick fixes at `offset` in the file at absolute `path`.
+/// Only offer to create files if the client `can_create_files`.
+pub fn code_actions(
+    paths: &BookPaths,
+    path: &Path,
+    source: &SourceText,
+    offset: usize,
+    documents: &Documents,
+    can_create_files: bool,
+) -> Vec<CodeActionOrCommand> {
+    let actions = match path == paths.summary_md {
+        true => summary_actions(paths, source, offset, documents, can_create_files),
+        false => fix_link(paths, path, source, offset, documents)
+            .into_iter()
+            .collect(),
+    };
+    actions
+        .into_iter()
+        .map(CodeActionOrCommand::CodeAction)
+        .collect()
+}
+
+/// For the `SUMMARY.md` entry on the line at `offset`:
+/// create its missing file, or insert the unlisted chapters under it.
+fn summary_actions(
+    paths: &BookPaths,
+    source: &SourceText,
+    offset: usize,
+    documents: &Documents,
+    can_create_files: bool,
+) -> Vec<CodeAction> {
+    let line = line_range(&source.text, offset);
+    let entries = parse_summary_entries(&source.text);
+    let Some(index) = entries
+        .iter()
+        .position(|entry| line.contains(&entry.range.start))
+    else {
+        return vec![];
+    };
+    let entry = &entries[index];
+    let mut actions = Vec::new();
+
+    let maybe_location = split_link_dest(&entry.location).filter(|(l, _)| !l.is_empty());
+    if let Some((location, _)) = maybe_location.filter(|_| can_create_files) {
+        let location = decode_summary_path(location);
+        let chapter_path = paths.src_dir.join(&location);
+        if !chapter_path.exists() && !documents.contains_key(&chapter_path) {
+            if let Ok(uri) = Url::from_file_path(&chapter_path) {
+                let content = format!("# {}\n", entry.name);
+                actions.push(CodeAction {
+                    title: format!("Create `{location}`"),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    edit: Some(create_file_edit(uri, content)),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    let indent = match entry.kind {
+        SummaryEntryKind::PartTitle => String::new(),
+        SummaryEntryKind::NumberedChapter(_) => {
+            let entry_line = &source.text[line_range(&source.text, entry.range.start)];
+            let entry_indent = entry_line.len() - entry_line.trim_start().len();
+            format!("{}    ", &entry_line[..entry_indent])
+        }
+        // Prefix and suffix chapters cannot have sub-chapters.
+        _ => return actions,
+    };
+    // Insert after the last descendant of the entry.
+    let last_descendant = entries[index + 1..]
+        .iter()
+        .take_while(|descendant| descendant.level > entry.level)
+        .last()
+        .unwrap_or(entry);
+    let insert_at = line_range(&source.text, last_descendant.range.start).end;
+    let (prefix, insert_at) = match source.text[..insert_at].ends_with('\n') {
+        true => ("", insert_at),
+        false => ("\n", source.text.len()),
+    };
+    let Ok(summary_uri) = Url::from_file_path(&paths.summary_md) else {
+        return actions;
+    };
+    for (title, chapter_path) in unlisted_chapters(paths, documents) {
+        let location = relative_link_path(&paths.summary_md, &chapter_path);
+        let title = match title.is_empty() {
+            true => location.trim_end_matches(".md").into(),
+            false => title,
+        };
+        let new_text = format!(
+            "{prefix}{indent}- [{title}]({})\n",
+            encode_summary_path(&location)
+        );
+        let position = source.position(insert_at);
+        let edit = TextEdit::new(lsp_types::Range::new(position, position), new_text);
+        let changes = HashMap::from([(summary_uri.clone(), vec![edit])]);
+        actions.push(CodeAction {
+            title: format!("Add `{location}` under `{}`", entry.name),
+            kind: Some(CodeActionKind::QUICKFIX),
+            edit: Some(WorkspaceEdit::new(changes)),
+            ..Default::default()
+        });
+    }
+    actions
+}
+
+/// Replace the broken target of the link at `offset` with the closest
+/// existing chapter, or its broken fragment with the closest heading ID.
+fn fix_link(
+    paths: &BookPaths,
+    path: &Path,
+    source: &SourceText,
+    offset: usize,
+    documents: &Documents,
+) -> Option<CodeAction> {
+    let link = parse_chapter(&source.text)
+        .links
+        .into_iter()
+        .find(|link| link.range.contains(&offset))?;
+    let (link_path, fragment) = link.path_n_fragment()?;
+    if !link_path.is_empty() && !link_path.ends_with(".md") {
+        return None;
+    }
+    let target = link_target(path, link_path);
+    let (broken, range, replacement) = match read_source(documents, &target) {
+        None => {
+            let chapter_paths = markdown_files(&paths.src_dir, documents);
+            let candidates = chapter_paths
+                .iter()
+                .filter(|chapter_path| **chapter_path != paths.summary_md)
+                .map(|chapter_path| relative_link_path(path, chapter_path));
+            let replacement = closest(&decode_link_part(link_path), candidates)?;
+            let replacement = encode_link_path(&replacement);
+            (link_path, link.path_range(&source.text)?, replacement)
+        }
+        Some(target_source) => {
+            let (fragment, decoded) = (fragment?, link.fragment.as_deref()?);
+            let ids = AnchorIds::new(&target, &target_source.text, documents);
+            if ids.contains(decoded) {
+                return None;
+            }
+            let headings = chapter_headings(&target, &target_source.text, documents);
+            let replacement = closest(decoded, headings.into_iter().map(|h| h.id))?;
+            (fragment, link.fragment_range(&source.text)?, replacement)
+        }
+    };
+    let uri = Url::from_file_path(path).ok()?;
+    let edit = TextEdit::new(source.range(&range), replacement.clone());
+    Some(CodeAction {
+        title: format!("Replace `{broken}` with `{replacement}`"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit::new(HashMap::from([(uri, vec![edit])]))),
+        is_preferred: Some(true),
+        ..Default::default()
+    })
+}
+
+/// Edit creating the file at `uri` with `content`, unless it exists.
+fn create_file_edit(uri: Url, content: String) -> WorkspaceEdit {
+    let create = DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+        uri: uri.clone(),
+        options: Some(CreateFileOptions {
+            overwrite: Some(false),
+            ignore_if_exists: Some(true),
+        }),
+        annotation_id: None,
+    }));
+    let write = DocumentChangeOperation::Edit(TextDocumentEdit {
+        text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+        edits: vec![OneOf::Left(TextEdit::new(Default::default(), content))],
+    });
+    WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(vec![create, write])),
+        ..Default::default()
+    }
+}
+
+/// Range of the line containing `offset` in `text`, including the newline.
+fn line_range(text: &str, offset: usize) -> Range<usize> {
+    let start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let end = text[offset..]
+        .find('\n')
+        .map_or(text.len(), |i| offset + i + 1);
+    start..end
+}
+
+/// The candidate with the smallest edit distance to `target`,
+/// if it is within a third of the length of `target`.
+fn closest(target: &str, candidates: impl IntoIterator<Item = String>) -> Option<String> {
+    // NOTE: Same threshold as rustc's suggestions for misspelled names.
+    let max_distance = target.chars().count().max(3) / 3;
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(target, &candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between `a` and `b` in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn documents() -> Documents {
+        open_documents([
+            (
+                "/book/src/SUMMARY.md",
+                "# Summary\n\n- [One](one.md)\n- [Missing](missing%20one.md)\n",
+            ),
+            (
+                "/book/src/one.md",
+                "# One\n\n[Two](tow%20%28b%29.md) [Self](#onne)\n",
+            ),
+            ("/book/src/two (b).md", "# Two\n"),
+        ])
+    }
+
+    /// Titles and new texts of the quick fixes right after `needle`
+    /// in the file at `path`.
+    fn fixes(path: &str, needle: &str) -> Vec<(String, Vec<String>)> {
+        let documents = documents();
+        let actions = at_needle(&documents, path, needle, |paths, path, source, offset| {
+            let offset = offset + needle.len();
+            code_actions(paths, path, source, offset, &documents, true)
+        });
+        actions
+            .into_iter()
+            .map(|action| {
+                let CodeActionOrCommand::CodeAction(action) = action else {
+                    panic!("Not a code action.");
+                };
+                let edit = action.edit.unwrap();
+                let new_texts = match (edit.changes, edit.document_changes) {
+                    (Some(changes), _) => changes
+                        .into_values()
+                        .flatten()
+                        .map(|edit| edit.new_text)
+                        .collect(),
+                    (_, Some(DocumentChanges::Operations(operations))) => operations
+                        .into_iter()
+                        .filter_map(|operation| match operation {
+                            DocumentChangeOperation::Edit(edit) => Some(edit.edits),
+                            DocumentChangeOperation::Op(_) => None,
+                        })
+                        .flatten()
+                        .map(|edit| match edit {
+                            OneOf::Left(edit) => edit.new_text,
+                            OneOf::Right(edit) => edit.text_edit.new_text,
+                        })
+                        .collect(),
+                    _ => vec![],
+                };
+                (action.title, new_texts)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn create_missing_chapter() {
+        let fixes = fixes("/book/src/SUMMARY.md", "- [Missing]");
+        assert_eq!(
+            fixes[0],
+            ("Create `missing one.md`".into(), vec!["# Missing\n".into()])
+        );
+    }
+
+    #[test]
+    fn add_orphan_to_summary() {
+        assert_eq!(
+            fixes("/book/src/SUMMARY.md", "- [One]"),
+            [(
+                "Add `two (b).md` under `One`".into(),
+                vec!["    - [Two](two%20\\(b\\).md)\n".into()]
+            )]
+        );
+    }
+
+    #[test]
+    fn fix_broken_links() {
+        assert_eq!(
+            fixes("/book/src/one.md", "[Two"),
+            [(
+                "Replace `tow%20%28b%29.md` with `two%20%28b%29.md`".into(),
+                vec!["two%20%28b%29.md".into()]
+            )]
+        );
+        assert_eq!(
+            fixes("/book/src/one.md", "[Self"),
+            [("Replace `onne` with `one`".into(), vec!["one".into()])]
+        );
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("intro.md", "intro.md"), 0);
+        assert_eq!(edit_distance("ch1.md", "ch2.md"), 1);
+        assert_eq!(edit_distance("ab", "ba"), 2);
+        // Characters, not bytes.
+        assert_eq!(edit_distance("café", "cafe"), 1);
+    }
+
+    #[test]
+    fn closest_within_threshold() {
+        let candidates = || ["intro.md", "chapter-1.md", "outro.md"].map(String::from);
+        assert_eq!(
+            closest("chaptr-1.md", candidates()),
+            Some("chapter-1.md".into())
+        );
+        assert_eq!(closest("intro.md", candidates()), Some("intro.md".into()));
+        assert_eq!(closest("completely-else.md", candidates()), None);
+        assert_eq!(closest("ab", ["xy".into()]), None);
+        assert_eq!(closest("ab", ["ac".into()]), Some("ac".into()));
+    }
+}
@@ -0,0 +1,181 @@
+use super::*;
+
+static LINK_FRAGMENT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\]\(([^)\s#]*)#([^)\s#]*)$").unwrap());
+static LINK_PATH: Lazy<Regex> = Lazy::new(|| Regex::new(r"\]\(([^)\s#]*)$").unwrap());
+static INCLUDE_ANCHOR: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\{\{\s*#(?:include|rustdoc_include)\s+([^\s:}]+):([\w_-]*)$").unwrap()
+});
+
+/// Completions at `offset` in the file at absolute `path`:
+/// heading IDs after `#` in a link, chapter paths in a link,
+/// unlisted Markdown files in `SUMMARY.md`,
+/// and `ANCHOR:` names in an include directive.
+pub fn complete(
+    paths: &BookPaths,
+    path: &Path,
+    source: &SourceText,
+    offset: usize,
+    documents: &Documents,
+) -> Vec<CompletionItem> {
+    let line_start = source.text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = &source.text[line_start..offset];
+    // Range to replace with the completion: the partial word typed.
+    let replace_range = |cap: &regex::Captures, group| {
+        let start = line_start + cap.get(group).map_or(line.len(), |m| m.start());
+        start..offset
+    };
+
+    if let Some(cap) = LINK_FRAGMENT.captures(line) {
+        let target = link_target(path, &cap[1]);
+        let Some(target_source) = read_source(documents, &target) else {
+            return vec![];
+        };
+        let range = replace_range(&cap, 2);
+        return chapter_headings(&target, &target_source.text, documents)
+            .into_iter()
+            .map(|heading| {
+                let kind = CompletionItemKind::REFERENCE;
+                completion_item(heading.id, Some(heading.title), kind, source, &range)
+            })
+            .collect();
+    }
+
+    if let Some(cap) = INCLUDE_ANCHOR.captures(line) {
+        let included_path = resolve_relative(path, &cap[1]);
+        let Some(included) = read_source(documents, &included_path) else {
+            return vec![];
+        };
+        let range = replace_range(&cap, 2);
+        return anchors(&included.text)
+            .into_iter()
+            .map(|(anchor, line_index)| {
+                let detail = Some(format!("Line {}", line_index + 1));
+                let kind = CompletionItemKind::REFERENCE;
+                completion_item(anchor, detail, kind, source, &range)
+            })
+            .collect();
+    }
+
+    if let Some(cap) = LINK_PATH.captures(line) {
+        let range = replace_range(&cap, 1);
+        let (chapters, encode): (_, fn(&str) -> String) = match path == paths.summary_md {
+            true => (unlisted_chapters(paths, documents), encode_summary_path),
+            false => (summary_chapters(paths, documents), encode_link_path),
+        };
+        return chapters
+            .into_iter()
+            .filter(|(_, chapter_path)| chapter_path != path)
+            .map(|(title, chapter_path)| {
+                let label = encode(&relative_link_path(path, &chapter_path));
+                let kind = CompletionItemKind::FILE;
+                completion_item(label, Some(title), kind, source, &range)
+            })
+            .collect();
+    }
+
+    vec![]
+}
+
+fn completion_item(
+    label: String,
+    detail: Option<String>,
+    kind: CompletionItemKind,
+    source: &SourceText,
+    range: &Range<usize>,
+) -> CompletionItem {
+    let edit = TextEdit::new(source.range(range), label.clone());
+    CompletionItem {
+        label,
+        kind: Some(kind),
+        detail,
+        text_edit: Some(CompletionTextEdit::Edit(edit)),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn documents() -> Documents {
+        open_documents([
+            (
+                "/book/src/SUMMARY.md",
+                "# Summary\n\n- [One](one.md)\n- [Two](sub/two%20(b).md)\n- [New](\n",
+            ),
+            (
+                "/book/src/one.md",
+                "# One\n\n[a](sub/two%20%28b%29.md#) [b]() {{#include code.rs:}}\n",
+            ),
+            ("/book/src/sub/two (b).md", "# Two\n\n## Café au lait\n"),
+            ("/book/src/new (draft).md", "# Draft\n"),
+            (
+                "/book/src/code.rs",
+                "// ANCHOR: main\nfn main() {}\n// ANCHOR_END: main\n",
+            ),
+        ])
+    }
+
+    /// Labels, details, and new texts of the completions right after
+    /// `needle` in the file at `path`.
+    fn completions(path: &str, needle: &str) -> Vec<(String, String, String)> {
+        let documents = documents();
+        let items = at_needle(&documents, path, needle, |paths, path, source, offset| {
+            complete(paths, path, source, offset + needle.len(), &documents)
+        });
+        items
+            .into_iter()
+            .map(|item| {
+                let Some(CompletionTextEdit::Edit(edit)) = item.text_edit else {
+                    panic!("No text edit.");
+                };
+                (item.label, item.detail.unwrap_or_default(), edit.new_text)
+            })
+            .collect()
+    }
+
+    fn owned(items: &[(&str, &str, &str)]) -> Vec<(String, String, String)> {
+        items
+            .iter()
+            .map(|(a, b, c)| ((*a).into(), (*b).into(), (*c).into()))
+            .collect()
+    }
+
+    #[test]
+    fn complete_chapter_paths_encoded() {
+        let two = "sub/two%20%28b%29.md";
+        assert_eq!(
+            completions("/book/src/one.md", "[b]("),
+            owned(&[(two, "Two", two)])
+        );
+    }
+
+    #[test]
+    fn complete_heading_fragments() {
+        assert_eq!(
+            completions("/book/src/one.md", "[a](sub/two%20%28b%29.md#"),
+            owned(&[
+                ("two", "Two", "two"),
+                ("café-au-lait", "Café au lait", "café-au-lait"),
+            ])
+        );
+    }
+
+    #[test]
+    fn complete_include_anchors() {
+        assert_eq!(
+            completions("/book/src/one.md", "{{#include code.rs:"),
+            owned(&[("main", "Line 1", "main")])
+        );
+    }
+
+    #[test]
+    fn complete_unlisted_files_in_summary() {
+        let new = r"new%20\(draft\).md";
+        assert_eq!(
+            completions("/book/src/SUMMARY.md", "- [New]("),
+            owned(&[(new, "Draft", new)])
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use super::*;
+
+/// The `source` of the diagnostics we publish.
+pub const DIAGNOSTIC_SOURCE: &str = "mdbook_ls";
+
+/// Diagnose the chapter at absolute `path`.
+pub fn diagnose_chapter(
+    book: &BookStructure,
+    path: &Path,
+    source: &SourceText,
+    documents: &Documents,
+    settings: &DiagnosticSettings,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    if settings.links {
+        diagnostics.extend(diagnose_links(book, path, source, documents));
+    }
+    if settings.includes {
+        diagnostics.extend(diagnose_includes(path, source, documents));
+    }
+    diagnostics
+}
+
+/// Diagnose the links in the chapter at absolute `path` whose target
+/// chapter does not exist or whose fragment matches no heading ID.
+pub fn diagnose_links(
+    book: &BookStructure,
+    path: &Path,
+    source: &SourceText,
+    documents: &Documents,
+) -> Vec<Diagnostic> {
+    let parsed = parse_chapter(&source.text);
+    let mut path2ids = HashMap::new();
+    let mut diagnostics = Vec::new();
+    for link in &parsed.links {
+        let Some((link_path, fragment)) = link.path_n_fragment() else {
+            continue;
+        };
+        if !(link_path.is_empty() || link_path.ends_with(".md")) {
+            // Not a chapter.
+            continue;
+        }
+        let target = link_target(path, link_path);
+        if target != path {
+            if !target.is_file() && !documents.contains_key(&target) {
+                let message = format!("`{link_path}` does not exist.");
+                diagnostics.push(make_diagnostic(source, &link.dest_range, ERROR, message));
+                continue;
+            }
+            if !book.chapter_paths.contains(&target) {
+                let message =
+                    format!("`{link_path}` is not listed in SUMMARY.md, so it is not rendered.");
+                diagnostics.push(make_diagnostic(source, &link.dest_range, WARNING, message));
+            }
+        }
+        let (Some(fragment), Some(decoded)) = (fragment, &link.fragment) else {
+            continue;
+        };
+        let ids = path2ids.entry(target.clone()).or_insert_with(|| {
+            let maybe_target_source = match target == path {
+                true => Some(Cow::Borrowed(source)),
+                false => read_source(documents, &target).map(Cow::Owned),
+            };
+            maybe_target_source.map(|s| AnchorIds::new(&target, &s.text, documents))
+        });
+        if let Some(ids) = ids {
+            if !ids.contains(decoded) {
+                let message = match link_path {
+                    "" => format!("No heading with ID `{fragment}` in this chapter."),
+                    _ => format!("No heading with ID `{fragment}` in `{link_path}`."),
+                };
+                diagnostics.push(make_diagnostic(source, &link.dest_range, ERROR, message));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Diagnose the include directives in the chapter at absolute `path` whose
+/// file does not exist, whose anchor is not in the file,
+/// or whose line range goes past the end of the file.
+pub fn diagnose_includes(
+    path: &Path,
+    source: &SourceText,
+    documents: &Documents,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for include in parse_includes(&source.text) {
+        let included_path = resolve_relative(path, &include.path);
+        let Some(included) = read_source(documents, &included_path) else {
+            let message = format!("`{}` does not exist.", include.path);
+            diagnostics.push(make_diagnostic(source, &include.range, ERROR, message));
+            continue;
+        };
+        let n_lines = included.text.lines().count();
+        let (severity, message) = match include.lines {
+            LineSelector::Anchor(anchor) => {
+                if anchors(&included.text)
+                    .iter()
+                    .any(|(name, _)| *name == anchor)
+                {
+                    continue;
+                }
+                let message = format!("No `ANCHOR: {anchor}` in `{}`.", include.path);
+                (ERROR, message)
+            }
+            LineSelector::Range { start, .. } if start >= n_lines => {
+                let message = format!(
+                    "The line range starts at line {}, but `{}` only has {n_lines} lines.",
+                    start + 1,
+                    include.path
+                );
+                (ERROR, message)
+            }
+            LineSelector::Range { end: Some(end), .. } if end > n_lines => {
+                let message = format!(
+                    "The line range ends at line {end}, but `{}` only has {n_lines} lines.",
+                    include.path
+                );
+                (WARNING, message)
+            }
+            LineSelector::Range { .. } => continue,
+        };
+        diagnostics.push(make_diagnostic(source, &include.range, severity, message));
+    }
+    diagnostics
+}
+
+/// Diagnostic for a chapter that mdBook does not render, on its first line.
+pub fn orphan_diagnostic() -> Diagnostic {
+    Diagnostic {
+        range: lsp_types::Range::new(Position::new(0, 0), Position::new(1, 0)),
+        severity: Some(WARNING),
+        source: Some(DIAGNOSTIC_SOURCE.into()),
+        message: "This file is not listed in SUMMARY.md, so it is not rendered.".into(),
+        ..Default::default()
+    }
+}
+
+/// Diagnose the `drafts` in `SUMMARY.md`, whose `source` is given.
+pub fn diagnose_drafts(source: &SourceText, drafts: &[SummaryEntry]) -> Vec<Diagnostic> {
+    drafts
+        .iter()
+        .map(|draft| {
+            let message = format!("`{}` is a draft chapter without a file.", draft.name);
+            make_diagnostic(source, &draft.range, INFORMATION, message)
+        })
+        .collect()
+}
+
+/// IDs a link fragment may point to in a chapter.
+pub struct AnchorIds {
+    heading_ids: HashSet<String>,
+    text: String,
+}
+
+impl AnchorIds {
+    /// The IDs in the chapter at absolute `path` with source `text`,
+    /// including those of the headings its `{{#include}}`s bring in.
+    pub fn new(path: &Path, text: &str, documents: &Documents) -> Self {
+        let text = resolve_includes(path, text, documents);
+        let heading_ids = parse_chapter(&text)
+            .headings
+            .into_iter()
+            .map(|heading| heading.id)
+            .collect();
+        Self {
+            heading_ids,
+            text: text.into(),
+        }
+    }
+
+    /// If `id` is a heading ID or an `id` attribute in raw HTML.
+    pub fn contains(&self, id: &str) -> bool {
+        self.heading_ids.contains(id) || self.text.contains(&format!("id=\"{id}\""))
+    }
+}
+
+pub fn make_diagnostic(
+    source: &SourceText,
+    range: &Range<usize>,
+    severity: DiagnosticSeverity,
+    message: String,
+) -> Diagnostic {
+    Diagnostic {
+        range: source.range(range),
+        severity: Some(severity),
+        source: Some(DIAGNOSTIC_SOURCE.into()),
+        message,
+        ..Default::default()
+    }
+}
+
+const ERROR: DiagnosticSeverity = DiagnosticSeverity::ERROR;
+const WARNING: DiagnosticSeverity = DiagnosticSeverity::WARNING;
+const INFORMATION: DiagnosticSeverity = DiagnosticSeverity::INFORMATION;
@@ -0,0 +1,439 @@
+use super::*;
+
+/// Open documents by their absolute paths.
+pub type Documents = HashMap<PathBuf, Document>;
+
+/// A document opened in the editor.
+#[derive(Clone, Debug)]
+pub struct Document {
+    pub uri: Url,
+    pub version: i32,
+    /// Buffer the incremental changes are applied to.
+    rope: Rope,
+    pub source: SourceText,
+    /// Changes that arrived before those of the versions preceding them,
+    /// by version.
+    pending_changes: BTreeMap<i32, Vec<TextDocumentContentChangeEvent>>,
+    /// If changes were dropped, so the text is stale until resynchronized.
+    pub out_of_sync: bool,
+}
+
+/// How many out-of-order changes to hold back before
+/// assuming the client skipped versions.
+const MAX_PENDING_CHANGES: usize = 16;
+
+impl Document {
+    pub fn new(uri: Url, version: i32, text: String) -> Self {
+        Self {
+            uri,
+            version,
+            rope: Rope::from_str(&text),
+            source: SourceText::new(text),
+            pending_changes: BTreeMap::new(),
+            out_of_sync: false,
+        }
+    }
+
+    /// Apply the content `changes` that bring the document to `version`.
+    ///
+    /// NOTE: Notification handlers run concurrently, so changes may arrive
+    /// out of order. Those of stale versions are ignored, and those arriving
+    /// before the previous version are queued until it arrives.
+    /// If too many queue up, the client skipped versions, so they are dropped
+    /// and an error is returned. The document then only tracks the versions
+    /// until a full replacement or [`Self::resync`].
+    /// Returns if the document changed.
+    pub fn update(
+        &mut self,
+        version: i32,
+        changes: Vec<TextDocumentContentChangeEvent>,
+    ) -> Result<bool> {
+        if version <= self.version {
+            warn!(self.version, version, "Ignoring stale changes.");
+            return Ok(false);
+        }
+        if self.out_of_sync && !is_full_replacement(&changes) {
+            debug!(self.version, version, "Ignoring changes while out of sync.");
+            self.version = version;
+            return Ok(false);
+        }
+        self.pending_changes.insert(version, changes);
+        // A full replacement supersedes the changes before it.
+        let maybe_full_version = self
+            .pending_changes
+            .iter()
+            .rfind(|(_, changes)| is_full_replacement(changes))
+            .map(|(&version, _)| version);
+        if let Some(full_version) = maybe_full_version {
+            self.pending_changes = self.pending_changes.split_off(&full_version);
+        }
+        let mut changed = false;
+        while let Some(entry) = self.pending_changes.first_entry() {
+            let is_next = *entry.key() == self.version + 1;
+            if !(is_next || is_full_replacement(entry.get())) {
+                break;
+            }
+            let (version, changes) = entry.remove_entry();
+            self.apply_changes(changes);
+            self.version = version;
+            self.out_of_sync = false;
+            changed = true;
+        }
+        match self.pending_changes.len() {
+            0 => {}
+            n_pending if n_pending > MAX_PENDING_CHANGES => {
+                let skipped_version = self.version + 1;
+                self.version = *self.pending_changes.last_key_value().unwrap().0;
+                self.pending_changes.clear();
+                self.out_of_sync = true;
+                bail!(
+                    "Never received version {skipped_version} of `{}`, so its text is out of sync until saved or reopened.",
+                    self.uri
+                );
+            }
+            _ => debug!(self.version, version, "Queuing out-of-order changes."),
+        }
+        Ok(changed)
+    }
+
+    /// Replace the text with `text`, known to be up to date,
+    /// e.g., the content saved to the disk.
+    /// Returns if the document changed.
+    pub fn resync(&mut self, text: String) -> bool {
+        self.pending_changes.clear();
+        self.out_of_sync = false;
+        if text == self.source.text {
+            return false;
+        }
+        self.rope = Rope::from_str(&text);
+        self.source = SourceText::new(text);
+        true
+    }
+
+    /// Apply the content `changes` in order.
+    pub fn apply_changes(&mut self, changes: Vec<TextDocumentContentChangeEvent>) {
+        for change in changes {
+            match change.range {
+                Some(range) => {
+                    let start = self.char_index(range.start);
+                    let end = self.char_index(range.end).max(start);
+                    self.rope.remove(start..end);
+                    self.rope.insert(start, &change.text);
+                }
+                None => self.rope = Rope::from_str(&change.text),
+            }
+        }
+        self.source = SourceText::new(self.rope.to_string());
+    }
+
+    /// Convert the LSP `position` to a char index, clamped to the end of
+    /// the content of its line, before the line break.
+    fn char_index(&self, position: Position) -> usize {
+        let line = position.line as usize;
+        if line >= self.rope.len_lines() {
+            return self.rope.len_chars();
+        }
+        let line_start = self.rope.line_to_char(line);
+        let line_slice = self.rope.line(line);
+        let mut chars = line_slice.chars_at(line_slice.len_chars());
+        let mut content_end = line_start + line_slice.len_chars();
+        while let Some('\n' | '\r') = chars.prev() {
+            content_end -= 1;
+        }
+        let line_start_utf16 = self.rope.char_to_utf16_cu(line_start);
+        let utf16 = line_start_utf16 + position.character as usize;
+        let index = self
+            .rope
+            .utf16_cu_to_char(utf16.min(self.rope.len_utf16_cu()));
+        index.min(content_end)
+    }
+}
+
+/// If `changes` replace the whole document.
+fn is_full_replacement(changes: &[TextDocumentContentChangeEvent]) -> bool {
+    !changes.is_empty() && changes.iter().all(|change| change.range.is_none())
+}
+
+/// Text with its line starts,
+/// for converting between byte offsets and LSP positions.
+#[derive(Clone, Debug, Default)]
+pub struct SourceText {
+    pub text: String,
+    /// Byte offset of the start of each line.
+    line_starts: Vec<usize>,
+}
+
+impl SourceText {
+    pub fn new(text: String) -> Self {
+        // NOTE: LSP breaks lines at `\n`, `\r\n` and `\r`.
+        let bytes = text.as_bytes();
+        let line_starts = iter::once(0)
+            .chain(bytes.iter().enumerate().filter_map(|(index, byte)| {
+                let is_break = match byte {
+                    b'\n' => true,
+                    b'\r' => bytes.get(index + 1) != Some(&b'\n'),
+                    _ => false,
+                };
+                is_break.then_some(index + 1)
+            }))
+            .collect();
+        Self { text, line_starts }
+    }
+
+    /// Convert the byte `offset` to an LSP position in UTF-16 code units.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_text = &self.text[self.line_starts[line]..offset];
+        Position::new(line as u32, line_text.encode_utf16().count() as u32)
+    }
+
+    pub fn range(&self, range: &Range<usize>) -> lsp_types::Range {
+        lsp_types::Range::new(self.position(range.start), self.position(range.end))
+    }
+
+    /// Convert the LSP `position` to a byte offset, clamped to the text.
+    pub fn offset(&self, position: Position) -> usize {
+        let Some(&line_start) = self.line_starts.get(position.line as usize) else {
+            return self.text.len();
+        };
+        let mut n_utf16 = 0;
+        for (index, char) in self.text[line_start..].char_indices() {
+            if n_utf16 >= position.character as usize || matches!(char, '\n' | '\r') {
+                return line_start + index;
+            }
+            n_utf16 += char.len_utf16();
+        }
+        self.text.len()
+    }
+}
+
+/// Text of the file at `path`,
+/// from the open documents if it is open, otherwise from the disk.
+pub fn read_source(documents: &Documents, path: &Path) -> Option<SourceText> {
+    match documents.get(path) {
+        Some(document) => Some(document.source.clone()),
+        None => fs::read_to_string(path).ok().map(SourceText::new),
+    }
+}
+
+/// Documents with the given texts opened at their absolute paths.
+#[cfg(test)]
+pub fn open_documents<'a>(files: impl IntoIterator<Item = (&'a str, &'a str)>) -> Documents {
+    files
+        .into_iter()
+        .map(|(path, text)| {
+            let uri = Url::from_file_path(path).unwrap();
+            (path.into(), Document::new(uri, 0, text.into()))
+        })
+        .collect()
+}
+
+/// Paths of a book at `/book` with the default configuration.
+#[cfg(test)]
+pub fn test_book_paths() -> BookPaths {
+    BookPaths {
+        book_toml: "/book/book.toml".into(),
+        src_dir: "/book/src".into(),
+        summary_md: "/book/src/SUMMARY.md".into(),
+    }
+}
+
+/// Call `feature` with the paths of the book at `/book`,
+/// and the absolute `path`, source, and offset of `needle` in
+/// the file at `path` in `documents`.
+#[cfg(test)]
+pub fn at_needle<T>(
+    documents: &Documents,
+    path: &str,
+    needle: &str,
+    feature: impl FnOnce(&BookPaths, &Path, &SourceText, usize) -> T,
+) -> T {
+    let path = Path::new(path);
+    let source = read_source(documents, path).unwrap();
+    let offset = source.text.find(needle).unwrap();
+    feature(&test_book_paths(), path, &source, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(text: &str) -> Document {
+        let uri = Url::parse("file:///book/src/chapter.md").unwrap();
+        Document::new(uri, 0, text.into())
+    }
+
+    fn change(
+        (start_line, start_char): (u32, u32),
+        (end_line, end_char): (u32, u32),
+        text: &str,
+    ) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(lsp_types::Range::new(
+                Position::new(start_line, start_char),
+                Position::new(end_line, end_char),
+            )),
+            range_length: None,
+            text: text.into(),
+        }
+    }
+
+    fn full(text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn apply_changes_in_utf16_columns() {
+        let mut doc = document("a😀b\nc\n");
+        // The emoji takes two UTF-16 code units.
+        doc.apply_changes(vec![change((0, 3), (0, 4), "X")]);
+        assert_eq!(doc.source.text, "a😀X\nc\n");
+        doc.apply_changes(vec![change((0, 1), (0, 3), "")]);
+        assert_eq!(doc.source.text, "aX\nc\n");
+    }
+
+    #[test]
+    fn apply_multi_line_changes() {
+        let mut doc = document("one\ntwo\nthree\n");
+        doc.apply_changes(vec![change((0, 1), (2, 2), "--")]);
+        assert_eq!(doc.source.text, "o--ree\n");
+        doc.apply_changes(vec![change((0, 3), (0, 3), "\nnew\n")]);
+        assert_eq!(doc.source.text, "o--\nnew\nree\n");
+    }
+
+    #[test]
+    fn apply_changes_in_order() {
+        let mut doc = document("abc");
+        doc.apply_changes(vec![
+            change((0, 0), (0, 1), "x"),
+            change((0, 3), (0, 3), "!"),
+        ]);
+        assert_eq!(doc.source.text, "xbc!");
+    }
+
+    #[test]
+    fn apply_changes_at_end_of_document() {
+        let mut doc = document("ab\ncd\n");
+        doc.apply_changes(vec![change((2, 0), (2, 0), "ef")]);
+        assert_eq!(doc.source.text, "ab\ncd\nef");
+        doc.apply_changes(vec![change((9, 0), (9, 5), "!")]);
+        assert_eq!(doc.source.text, "ab\ncd\nef!");
+    }
+
+    #[test]
+    fn apply_full_replacement() {
+        let mut doc = document("ab\ncd\n");
+        doc.apply_changes(vec![change((0, 0), (0, 1), "x"), full("new\n")]);
+        assert_eq!(doc.source.text, "new\n");
+        doc.apply_changes(vec![full("newer"), change((0, 5), (0, 5), "!")]);
+        assert_eq!(doc.source.text, "newer!");
+    }
+
+    #[test]
+    fn clamp_columns_before_line_breaks() {
+        let mut doc = document("ab\ncd\r\nef\rgh");
+        doc.apply_changes(vec![change((0, 99), (0, 99), "!")]);
+        assert_eq!(doc.source.text, "ab!\ncd\r\nef\rgh");
+        doc.apply_changes(vec![change((1, 99), (1, 99), "!")]);
+        assert_eq!(doc.source.text, "ab!\ncd!\r\nef\rgh");
+        doc.apply_changes(vec![change((2, 99), (2, 99), "!")]);
+        assert_eq!(doc.source.text, "ab!\ncd!\r\nef!\rgh");
+    }
+
+    #[test]
+    fn break_lines_as_lsp_does() {
+        let mut doc = document("ab\u{2028}cd\nef\n");
+        doc.apply_changes(vec![change((1, 0), (1, 2), "XY")]);
+        assert_eq!(doc.source.text, "ab\u{2028}cd\nXY\n");
+
+        let source = SourceText::new("a\r\nb\rc\u{2028}d\ne".into());
+        assert_eq!(source.position(3), Position::new(1, 0));
+        assert_eq!(source.position(5), Position::new(2, 0));
+        assert_eq!(source.position(11), Position::new(3, 0));
+        assert_eq!(source.offset(Position::new(0, 9)), 1);
+        assert_eq!(source.offset(Position::new(1, 9)), 4);
+        assert_eq!(source.offset(Position::new(2, 9)), 10);
+    }
+
+    #[test]
+    fn update_versions_in_order() -> Result<()> {
+        let mut doc = document("");
+        assert!(!doc.update(0, vec![full("stale")])?);
+        // Version 2 arrives before version 1.
+        assert!(!doc.update(2, vec![change((0, 1), (0, 1), "b")])?);
+        assert_eq!(doc.source.text, "");
+        assert!(doc.update(1, vec![change((0, 0), (0, 0), "a")])?);
+        assert_eq!((doc.version, doc.source.text.as_str()), (2, "ab"));
+        // Full replacements need no previous version.
+        assert!(doc.update(5, vec![full("new")])?);
+        assert_eq!((doc.version, doc.source.text.as_str()), (5, "new"));
+        assert!(!doc.update(4, vec![full("stale")])?);
+        assert_eq!(doc.source.text, "new");
+        Ok(())
+    }
+
+    #[test]
+    fn queue_empty_changes_in_order() -> Result<()> {
+        let mut doc = document("a");
+        // Empty changes are not full replacements, so they wait for version 1.
+        assert!(!doc.update(2, vec![])?);
+        assert_eq!(doc.version, 0);
+        assert!(doc.update(1, vec![change((0, 1), (0, 1), "b")])?);
+        assert_eq!((doc.version, doc.source.text.as_str()), (2, "ab"));
+        Ok(())
+    }
+
+    /// Skip version 1 and queue changes until they overflow.
+    fn fall_out_of_sync(doc: &mut Document) -> i32 {
+        let last_version = MAX_PENDING_CHANGES as i32 + 2;
+        for version in 2..last_version {
+            assert!(!doc
+                .update(version, vec![change((0, 0), (0, 0), "x")])
+                .unwrap());
+        }
+        assert!(doc
+            .update(last_version, vec![change((0, 0), (0, 0), "x")])
+            .is_err());
+        assert!(doc.out_of_sync && doc.pending_changes.is_empty());
+        last_version
+    }
+
+    #[test]
+    fn drop_changes_after_skipped_versions() -> Result<()> {
+        let mut doc = document("good");
+        let last_version = fall_out_of_sync(&mut doc);
+        assert_eq!(
+            (doc.version, doc.source.text.as_str()),
+            (last_version, "good")
+        );
+        // Incremental changes are ignored, but their versions are tracked.
+        assert!(!doc.update(30, vec![change((0, 0), (0, 0), "x")])?);
+        assert_eq!((doc.version, doc.source.text.as_str()), (30, "good"));
+        // A full replacement resynchronizes the document.
+        assert!(doc.update(31, vec![full("synced")])?);
+        assert_eq!((doc.version, doc.source.text.as_str()), (31, "synced"));
+        assert!(!doc.out_of_sync);
+        assert!(doc.update(32, vec![change((0, 6), (0, 6), "!")])?);
+        assert_eq!(doc.source.text, "synced!");
+        Ok(())
+    }
+
+    #[test]
+    fn resync_after_skipped_versions() -> Result<()> {
+        let mut doc = document("good");
+        fall_out_of_sync(&mut doc);
+        assert!(!doc.update(30, vec![change((0, 0), (0, 0), "x")])?);
+        // E.g., the text saved to the disk.
+        assert!(doc.resync("saved".into()));
+        assert!(!doc.out_of_sync);
+        assert!(doc.update(31, vec![change((0, 5), (0, 5), "!")])?);
+        assert_eq!((doc.version, doc.source.text.as_str()), (31, "saved!"));
+        assert!(!doc.resync("saved!".into()));
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use super::*;
+
+/// Hover for the include directive or link at `offset` in the chapter at
+/// absolute `path`: the included lines,
+/// or the target's title and first paragraph.
+pub fn hover(
+    paths: &BookPaths,
+    path: &Path,
+    source: &SourceText,
+    offset: usize,
+    documents: &Documents,
+) -> Option<Hover> {
+    let maybe_include = parse_includes(&source.text)
+        .into_iter()
+        .find(|include| include.range.contains(&offset));
+    if let Some(include) = maybe_include {
+        let included_path = resolve_relative(path, &include.path);
+        let included = read_source(documents, &included_path)?;
+        let language = match included_path.extension().and_then(|e| e.to_str()) {
+            Some("rs") => "rust",
+            Some("md") => "markdown",
+            Some(extension) => extension,
+            None => "",
+        };
+        let lines = include.take_lines(&included.text);
+        let fence = code_fence(&lines);
+        let value = format!(
+            "{fence}{language}\n{}\n{fence}",
+            lines.trim_end_matches('\n')
+        );
+        return Some(make_hover(value, source, &include.range));
+    }
+
+    let link = parse_chapter(&source.text)
+        .links
+        .into_iter()
+        .find(|link| link.range.contains(&offset))?;
+    let (link_path, _) = link.path_n_fragment()?;
+    let fragment = link.fragment.as_deref();
+    if (link_path, fragment) == ("", None) || !(link_path.is_empty() || link_path.ends_with(".md"))
+    {
+        return None;
+    }
+    let target = link_target(path, link_path);
+    let target_source = read_source(documents, &target)?;
+    let headings = chapter_headings(&target, &target_source.text, documents);
+    let (title, content_start) = match fragment {
+        Some(fragment) => {
+            let heading = headings
+                .into_iter()
+                .find(|heading| heading.id == fragment)?;
+            (heading.title, heading.range.end)
+        }
+        None => {
+            let chapters = summary_chapters(paths, documents);
+            let maybe_name = chapters.into_iter().find(|(_, path)| *path == target);
+            match (maybe_name, headings.into_iter().next()) {
+                (Some((name, _)), _) => (name, 0),
+                (None, Some(heading)) => (heading.title, 0),
+                (None, None) => (link_path.into(), 0),
+            }
+        }
+    };
+    let mut value = format!("**{title}**");
+    if let Some(paragraph) = first_paragraph(&target_source.text, content_start) {
+        value.push_str("\n\n");
+        value.push_str(paragraph);
+    }
+    Some(make_hover(value, source, &link.range))
+}
+
+/// Source of the first paragraph in `text` from byte `start`.
+fn first_paragraph(text: &str, start: usize) -> Option<&str> {
+    utils::new_cmark_parser(text, false)
+        .into_offset_iter()
+        .find_map(|(event, range)| match event {
+            Event::Start(Tag::Paragraph) if range.start >= start => Some(text[range].trim()),
+            _ => None,
+        })
+}
+
+/// A backtick fence longer than any backtick run in `code`.
+fn code_fence(code: &str) -> String {
+    let longest_run = code
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    "`".repeat(longest_run.max(2) + 1)
+}
+
+fn make_hover(value: String, source: &SourceText, range: &Range<usize>) -> Hover {
+    Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(source.range(range)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hover_text(needle: &str) -> Option<String> {
+        let documents = open_documents([
+            ("/book/src/SUMMARY.md", "# Summary\n\n- [Second](two.md)\n"),
+            (
+                "/book/src/one.md",
+                "[Two](two.md#caf%C3%A9) [Whole](two.md) [Web](https://a.b)\n\n{{#include code.rs:2}}\n",
+            ),
+            ("/book/src/two.md", "# Title\n\nIntro.\n\n# Café\n\nFirst\nparagraph.\n\nSecond.\n"),
+            ("/book/src/code.rs", "use a;\nfn main() {}\n"),
+        ]);
+        let hover = at_needle(
+            &documents,
+            "/book/src/one.md",
+            needle,
+            |paths, path, source, offset| hover(paths, path, source, offset, &documents),
+        )?;
+        match hover.contents {
+            HoverContents::Markup(content) => Some(content.value),
+            contents => panic!("Unexpected hover contents {contents:?}."),
+        }
+    }
+
+    #[test]
+    fn hover_links_and_includes() {
+        assert_eq!(
+            hover_text("[Two]").unwrap(),
+            "**Café**\n\nFirst\nparagraph."
+        );
+        // The name in `SUMMARY.md` takes precedence over the first heading.
+        assert_eq!(hover_text("[Whole]").unwrap(), "**Second**\n\nIntro.");
+        assert_eq!(hover_text("[Web]"), None);
+        assert_eq!(
+            hover_text("{{#include").unwrap(),
+            "```rust\nfn main() {}\n```"
+        );
+    }
+}
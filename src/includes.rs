@@ -0,0 +1,332 @@
+use super::*;
+
+// NOTE: Below is adapted from
+// <https://github.com/rust-lang/mdBook/blob/3bdcc0a5a6f3c85dd751350774261dbc357b02bd/src/preprocess/links.rs>.
+
+/// A `{{#include}}`, `{{#rustdoc_include}}` or `{{#playground}}` directive
+/// handled by mdBook's `links` preprocessor.
+#[derive(Clone, Debug)]
+pub struct IncludeDirective {
+    pub kind: IncludeKind,
+    /// Path of the included file relative to the chapter's directory.
+    pub path: String,
+    pub path_range: Range<usize>,
+    pub lines: LineSelector,
+    /// Range of the whole directive.
+    pub range: Range<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IncludeKind {
+    Include,
+    RustdocInclude,
+    Playground,
+}
+
+/// Which lines of the included file to take.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LineSelector {
+    /// 0-based line indexes, `end` exclusive.
+    Range {
+        start: usize,
+        end: Option<usize>,
+    },
+    Anchor(String),
+}
+
+static ANCHOR_START: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"ANCHOR:\s*(?P<anchor_name>[\w_-]+)").unwrap());
+
+/// Find all include directives in `text`, skipping escaped ones.
+pub fn parse_includes(text: &str) -> Vec<IncludeDirective> {
+    LINK_DIRECTIVE
+        .captures_iter(text)
+        .filter_map(|cap| {
+            let (kind, rest) = (cap.get(1)?, cap.get(2)?);
+            let kind = match kind.as_str() {
+                "include" => IncludeKind::Include,
+                "rustdoc_include" => IncludeKind::RustdocInclude,
+                "playground" | "playpen" => IncludeKind::Playground,
+                _ => return None,
+            };
+            let file_arg = rest.as_str().split_whitespace().next()?;
+            let file_arg_start = rest.start() + rest.as_str().find(file_arg)?;
+            let (path, lines) = match kind {
+                IncludeKind::Playground => (file_arg, LineSelector::ALL),
+                _ => match file_arg.split_once(':') {
+                    Some((path, lines)) => (path, LineSelector::parse(lines)),
+                    None => (file_arg, LineSelector::ALL),
+                },
+            };
+            Some(IncludeDirective {
+                kind,
+                path: path.into(),
+                path_range: file_arg_start..file_arg_start + path.len(),
+                lines,
+                range: cap.get(0)?.range(),
+            })
+        })
+        .collect()
+}
+
+/// How deep mdBook resolves includes within includes.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// `text` of the file at absolute `path` with its `{{#include}}`s
+/// resolved like mdBook's `links` preprocessor does,
+/// leaving the directives whose file cannot be read as they are.
+pub fn resolve_includes<'a>(path: &Path, text: &'a str, documents: &Documents) -> Cow<'a, str> {
+    resolve_includes_to_depth(path, text, documents, 0).0
+}
+
+/// Headings of the chapter at absolute `path` with source `text`,
+/// including those its `{{#include}}`s bring in,
+/// with the IDs mdBook assigns to them in the rendered chapter.
+/// The ranges of the included headings are those of their directives,
+/// and they have no content range, as their text is not in `text`.
+pub fn chapter_headings(path: &Path, text: &str, documents: &Documents) -> Vec<Heading> {
+    let (resolved, splices) = resolve_includes_to_depth(path, text, documents, 0);
+    let mut headings = parse_chapter(&resolved).headings;
+    if splices.is_empty() {
+        return headings;
+    }
+    for heading in &mut headings {
+        heading.content_range = heading
+            .content_range
+            .take()
+            .filter(|range| {
+                !splices.iter().any(|splice| {
+                    splice.resolved.start < range.end && range.start < splice.resolved.end
+                })
+            })
+            .map(|range| {
+                source_offset(&splices, range.start, false)
+                    ..source_offset(&splices, range.end, true)
+            });
+        heading.range = source_offset(&splices, heading.range.start, false)
+            ..source_offset(&splices, heading.range.end, true);
+    }
+    headings
+}
+
+/// An included file's lines spliced into a chapter in place of the directive.
+struct Splice {
+    /// Range of the included lines in the resolved text.
+    resolved: Range<usize>,
+    /// Range of the directive in the source text.
+    directive: Range<usize>,
+}
+
+/// Offset in the source text of `offset` in the text resolved with `splices`.
+/// An offset in an included file maps to the start of its directive,
+/// or to its end if `is_end`.
+fn source_offset(splices: &[Splice], offset: usize, is_end: bool) -> usize {
+    let is_at_or_after = |splice: &&Splice| match is_end {
+        true => splice.resolved.start < offset,
+        false => splice.resolved.start <= offset,
+    };
+    let Some(splice) = splices.iter().take_while(is_at_or_after).last() else {
+        return offset;
+    };
+    match (
+        is_end,
+        offset <= splice.resolved.end,
+        offset < splice.resolved.end,
+    ) {
+        (true, true, _) => splice.directive.end,
+        (false, _, true) => splice.directive.start,
+        _ => offset - splice.resolved.end + splice.directive.end,
+    }
+}
+
+/// `text` with its `{{#include}}`s resolved,
+/// and where the top-level ones are spliced in.
+fn resolve_includes_to_depth<'a>(
+    path: &Path,
+    text: &'a str,
+    documents: &Documents,
+    depth: usize,
+) -> (Cow<'a, str>, Vec<Splice>) {
+    let includes = parse_includes(text);
+    if includes.is_empty() || depth >= MAX_INCLUDE_DEPTH {
+        return (Cow::Borrowed(text), vec![]);
+    }
+    let mut resolved = String::with_capacity(text.len());
+    let mut splices = vec![];
+    let mut previous_end = 0;
+    // NOTE: The other directives end up in code blocks,
+    // so they never add headings.
+    for include in includes.iter().filter(|i| i.kind == IncludeKind::Include) {
+        let included_path = resolve_relative(path, &include.path);
+        let Some(included) = read_source(documents, &included_path) else {
+            continue;
+        };
+        let lines = include.take_lines(&included.text);
+        let (lines, _) = resolve_includes_to_depth(&included_path, &lines, documents, depth + 1);
+        resolved.push_str(&text[previous_end..include.range.start]);
+        let start = resolved.len();
+        resolved.push_str(&lines);
+        splices.push(Splice {
+            resolved: start..resolved.len(),
+            directive: include.range.clone(),
+        });
+        previous_end = include.range.end;
+    }
+    resolved.push_str(&text[previous_end..]);
+    (Cow::Owned(resolved), splices)
+}
+
+impl IncludeDirective {
+    /// The lines of the included file's `text` that
+    /// mdBook splices into the chapter.
+    pub fn take_lines(&self, text: &str) -> String {
+        use utils::{
+            take_anchored_lines, take_lines, take_rustdoc_include_anchored_lines,
+            take_rustdoc_include_lines,
+        };
+        match (self.kind, &self.lines) {
+            (IncludeKind::Playground, _) => text.into(),
+            (IncludeKind::Include, LineSelector::Anchor(anchor)) => {
+                take_anchored_lines(text, anchor)
+            }
+            (IncludeKind::Include, &LineSelector::Range { start, end }) => match end {
+                Some(end) => take_lines(text, start..end),
+                None => take_lines(text, start..),
+            },
+            (IncludeKind::RustdocInclude, LineSelector::Anchor(anchor)) => {
+                take_rustdoc_include_anchored_lines(text, anchor)
+            }
+            (IncludeKind::RustdocInclude, &LineSelector::Range { start, end }) => match end {
+                Some(end) => take_rustdoc_include_lines(text, start..end),
+                None => take_rustdoc_include_lines(text, start..),
+            },
+        }
+    }
+}
+
+impl LineSelector {
+    pub const ALL: Self = Self::Range {
+        start: 0,
+        end: None,
+    };
+
+    /// Parse the part after the path, e.g., `anchor` or `2:10`.
+    fn parse(s: &str) -> Self {
+        let mut parts = s.splitn(3, ':').fuse();
+        let start = match parts.next().map(|s| (s, s.parse::<usize>())) {
+            // Subtract 1 since line numbers usually begin with 1.
+            Some((_, Ok(value))) => Some(value.saturating_sub(1)),
+            Some(("", _)) | None => None,
+            Some((anchor, Err(_))) => return Self::Anchor(anchor.into()),
+        };
+        // If `end` is empty or cannot be parsed, treat this include as
+        // a range with only a start bound. However, if `end` is not specified,
+        // include only the single line specified by `start`.
+        let end = parts.next().map(|s| s.parse::<usize>());
+        match (start, end) {
+            (Some(start), Some(Ok(end))) => Self::Range {
+                start,
+                end: Some(end),
+            },
+            (Some(start), Some(Err(_))) => Self::Range { start, end: None },
+            (Some(start), None) => Self::Range {
+                start,
+                end: Some(start + 1),
+            },
+            (None, Some(Ok(end))) => Self::Range {
+                start: 0,
+                end: Some(end),
+            },
+            (None, None) | (None, Some(Err(_))) => Self::ALL,
+        }
+    }
+}
+
+/// Names of the `ANCHOR:`s in `text`, with their 0-based line indexes.
+pub fn anchors(text: &str) -> Vec<(String, usize)> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let cap = ANCHOR_START.captures(line)?;
+            Some((cap["anchor_name"].to_owned(), index))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: usize, end: Option<usize>) -> LineSelector {
+        LineSelector::Range { start, end }
+    }
+
+    #[test]
+    fn parse_line_ranges() {
+        assert_eq!(LineSelector::parse(""), LineSelector::ALL);
+        assert_eq!(LineSelector::parse(":"), LineSelector::ALL);
+        assert_eq!(LineSelector::parse("3"), range(2, Some(3)));
+        assert_eq!(LineSelector::parse("3:"), range(2, None));
+        assert_eq!(LineSelector::parse("3:x"), range(2, None));
+        assert_eq!(LineSelector::parse(":5"), range(0, Some(5)));
+        assert_eq!(LineSelector::parse("2:5"), range(1, Some(5)));
+        assert_eq!(LineSelector::parse("0:5"), range(0, Some(5)));
+    }
+
+    #[test]
+    fn parse_anchors() {
+        assert_eq!(
+            LineSelector::parse("anchor"),
+            LineSelector::Anchor("anchor".into())
+        );
+        assert_eq!(
+            LineSelector::parse("my-anchor:2"),
+            LineSelector::Anchor("my-anchor".into())
+        );
+    }
+
+    #[test]
+    fn parse_include_directives() {
+        let text = "{{#include file.rs:2:5}}\n\\{{#include escaped.rs}}\n{{#playground a:b.rs}}";
+        let includes = parse_includes(text);
+        assert_eq!(includes.len(), 2);
+        assert_eq!(includes[0].kind, IncludeKind::Include);
+        assert_eq!(includes[0].path, "file.rs");
+        assert_eq!(&text[includes[0].path_range.clone()], "file.rs");
+        assert_eq!(includes[0].lines, range(1, Some(5)));
+        assert_eq!(includes[1].kind, IncludeKind::Playground);
+        assert_eq!(includes[1].path, "a:b.rs");
+        assert_eq!(includes[1].lines, LineSelector::ALL);
+    }
+
+    #[test]
+    fn number_headings_after_includes() {
+        let text = "# Intro\n\n{{#include part.md}}\n\n# Intro\n";
+        let documents = open_documents([
+            ("/book/src/chapter.md", text),
+            ("/book/src/part.md", "# Intro\n## Sub\n"),
+        ]);
+        let headings = chapter_headings(Path::new("/book/src/chapter.md"), text, &documents);
+        let directive = text.find("{{").unwrap()..text.find("}}").unwrap() + 2;
+        let summary: Vec<_> = headings
+            .iter()
+            .map(|h| (h.id.as_str(), h.range.clone(), h.content_range.clone()))
+            .collect();
+        let last_start = text.rfind('#').unwrap();
+        assert_eq!(
+            summary,
+            [
+                ("intro", 0..8, Some(2..7)),
+                ("intro-1", directive.start..directive.end, None),
+                // Up to the line break after the directive, as mdBook
+                // drops that at the end of the included lines.
+                ("sub", directive.start..directive.end + 1, None),
+                (
+                    "intro-2",
+                    last_start..text.len(),
+                    Some(last_start + 2..text.len() - 1)
+                ),
+            ]
+        );
+    }
+}
@@ -1,27 +1,88 @@
 use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    env, fs, iter,
     net::SocketAddr,
-    path::{Path, PathBuf},
+    ops::Range,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
 };
 
-use anyhow::Result;
-use mdbook_incremental_preview::previewing::*;
-use serde_json::Value;
+use anyhow::{bail, Context, Result};
+use ignore::WalkBuilder;
+use mdbook::{
+    book::{parse_summary, Link, Summary, SummaryItem},
+    utils,
+};
+use mdbook_incremental_preview::{
+    block_n_yield, book_paths::BookPaths, previewing::*, source_lines::LINK_DIRECTIVE,
+};
+use once_cell::sync::Lazy;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use pulldown_cmark::{html, Event, HeadingLevel, Parser, Tag, TagEnd};
+use regex::Regex;
+use ropey::Rope;
+use serde::Deserialize;
+use serde_json::{json, Value};
 use tokio::{
     io::{stdin, stdout},
+    spawn,
     sync::mpsc,
+    task::JoinHandle,
 };
 use tokio_gen_server::prelude::*;
-use tower_lsp::{LspService, Server};
+use tower_lsp::{
+    lsp_types::{
+        self, ClientCapabilities, CodeAction, CodeActionKind, CodeActionOrCommand, CompletionItem,
+        CompletionItemKind, CompletionTextEdit, CreateFile, CreateFileOptions, Diagnostic,
+        DiagnosticSeverity, DocumentChangeOperation, DocumentChanges, DocumentSymbol, Hover,
+        HoverContents, Location, MarkupContent, MarkupKind, OneOf,
+        OptionalVersionedTextDocumentIdentifier, Position, PrepareRenameResponse, ResourceOp,
+        ResourceOperationKind, ShowDocumentParams, SymbolInformation, SymbolKind,
+        TextDocumentContentChangeEvent, TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+    },
+    Client, LspService, Server,
+};
 use tracing::*;
 
+pub mod book_structure;
+pub mod code_actions;
+pub mod completing;
+pub mod diagnosing;
+pub mod documents;
+pub mod hovering;
+pub mod includes;
 pub mod lsp;
+pub mod navigating;
+pub mod parsing;
+pub mod renaming;
+pub mod server_state;
+pub mod settings;
+pub mod symbols;
+pub mod workspace_books;
 
+use book_structure::*;
+use code_actions::*;
+use completing::*;
+use diagnosing::*;
+use documents::*;
+use hovering::*;
+use includes::*;
 use lsp::*;
+use navigating::*;
+use parsing::*;
+use renaming::*;
+use server_state::*;
+use settings::*;
+use symbols::*;
+use workspace_books::*;
 
-pub async fn run_mdbook_ls() -> Result<()> {
+/// Serve the language server over stdio,
+/// updating the log filter with `set_log_filter` per the settings.
+pub async fn run_mdbook_ls(set_log_filter: SetLogFilter) -> Result<()> {
     let (stdin, stdout) = (stdin(), stdout());
-    let live_patcher = Previewer::try_new()?;
-    let (service, socket) = LspService::new(|client| MDBookLS::new(client, live_patcher));
+    let (service, socket) = LspService::new(|client| MDBookLS::new(client, set_log_filter));
     info!(?socket, "Starting mdBook-LS");
     Server::new(stdin, stdout, socket).serve(service).await;
     Ok(())
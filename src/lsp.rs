@@ -1,50 +1,295 @@
-use tower_lsp::{jsonrpc::Result, lsp_types::*, Client, LanguageServer};
+use tower_lsp::{jsonrpc::Result, lsp_types::*, LanguageServer};
 
 use super::*;
 
-#[derive(Debug)]
 pub struct MDBookLS {
     client: Client,
-    live_patcher_handle: ActorHandle<ActorMsg<Previewer>>,
-    live_patcher: ActorRef<Previewer>,
+    /// Previewers by the absolute paths of the roots of their books.
+    previewers: RwLock<HashMap<PathBuf, BookPreviewer>>,
+    /// Offsets from the configured preview port of the books whose preview
+    /// is open, by their roots, so each book is served at its own port.
+    preview_port_offsets: RwLock<HashMap<PathBuf, u16>>,
+    set_log_filter: SetLogFilter,
+    state: Arc<ServerState>,
 }
 
 impl MDBookLS {
-    pub fn new(client: Client, live_patcher: Previewer) -> Self {
-        let (tx, msg_receiver) = mpsc::channel(8);
-        let (live_patcher_handle, live_patcher) =
-            live_patcher.spawn_with_channel(tx.clone(), msg_receiver);
+    pub fn new(client: Client, set_log_filter: SetLogFilter) -> Self {
         Self {
             client,
-            live_patcher_handle,
-            live_patcher,
+            previewers: Default::default(),
+            preview_port_offsets: Default::default(),
+            set_log_filter,
+            state: Default::default(),
         }
     }
+
+    /// Apply `settings` to the log filter and the previewers,
+    /// and keep them for the diagnostics.
+    async fn apply_settings(&self, settings: Settings) {
+        info!(?settings, "Applying settings.");
+        if let Some(log_level) = &settings.log_level {
+            if let Err(err) = (self.set_log_filter)(log_level) {
+                warn!(?err, log_level, "Setting the log filter.");
+            }
+        }
+        let preview_settings = settings.preview_settings();
+        *self.state.settings.write().unwrap() = settings;
+        for (book_root, previewer) in self.all_previewers() {
+            let socket_address = self.preview_address(&book_root, preview_settings.socket_address);
+            let msg = PreviewInfo::Settings(PreviewSettings {
+                socket_address,
+                ..preview_settings.clone()
+            });
+            previewer.cast(msg).await.expect("Previewer died.");
+        }
+    }
+
+    /// Open the preview of the book containing `open_browser_at` if provided,
+    /// or otherwise the first book.
+    /// Each book is served at its own port,
+    /// offset from that of `socket_address` or the configured address.
+    /// Returns the URL of the preview.
+    async fn open_preview(
+        &self,
+        socket_address: Option<SocketAddr>,
+        open_browser_at: Option<PathBuf>,
+    ) -> Option<String> {
+        let Some(book_root) = self.pick_book_root(open_browser_at.as_deref()) else {
+            warn!(?open_browser_at, "No book to preview.");
+            return None;
+        };
+        let previewer = self
+            .previewers
+            .read()
+            .unwrap()
+            .get(&book_root)?
+            .previewer
+            .clone();
+        {
+            let mut offsets = self.preview_port_offsets.write().unwrap();
+            if !offsets.contains_key(&book_root) {
+                let taken: HashSet<_> = offsets.values().copied().collect();
+                let offset = (0..).find(|offset| !taken.contains(offset)).unwrap();
+                offsets.insert(book_root.clone(), offset);
+            }
+        }
+        let base_address =
+            socket_address.unwrap_or_else(|| self.state.settings.read().unwrap().preview_address);
+        let socket_address = self.preview_address(&book_root, base_address);
+        let msg = PreviewInfo::OpenPreview {
+            socket_address: Some(socket_address),
+            open_browser_at,
+        };
+        previewer.cast(msg).await.expect("Previewer died.");
+        let url = format!("http://{socket_address}/");
+        let message = format!("Previewing `{}` at {url}", book_root.display());
+        self.client.log_message(MessageType::INFO, message).await;
+        Some(url)
+    }
+
+    /// `base_address` with its port offset for the book at `book_root`.
+    fn preview_address(&self, book_root: &Path, base_address: SocketAddr) -> SocketAddr {
+        let offsets = self.preview_port_offsets.read().unwrap();
+        let offset = offsets.get(book_root).copied().unwrap_or_default();
+        let mut address = base_address;
+        // NOTE: Port 0 lets the OS pick a free port for each book.
+        if address.port() != 0 {
+            address.set_port(address.port().saturating_add(offset));
+        }
+        address
+    }
+
+    async fn publish_diagnostics(&self) {
+        self.state.publish_diagnostics(&self.client).await;
+    }
+
+    /// Ask the client to notify us of changes to the books' files on disk,
+    /// if it supports that.
+    async fn watch_files(&self) {
+        let can_register = self
+            .state
+            .client_capabilities
+            .read()
+            .unwrap()
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.did_change_watched_files)
+            .and_then(|watched_files| watched_files.dynamic_registration)
+            .unwrap_or(false);
+        if !can_register {
+            return info!("The client cannot watch files for us.");
+        }
+        let watchers = ["**/*.md", "**/book.toml"]
+            .map(|glob| FileSystemWatcher {
+                glob_pattern: GlobPattern::String(glob.into()),
+                kind: None,
+            })
+            .into();
+        let options = DidChangeWatchedFilesRegistrationOptions { watchers };
+        let registration = Registration {
+            id: "watch_book_files".into(),
+            method: "workspace/didChangeWatchedFiles".into(),
+            register_options: Some(json!(options)),
+        };
+        if let Err(err) = self.client.register_capability(vec![registration]).await {
+            warn!(?err, "Registering the file watchers.");
+        }
+    }
+
+    fn book_paths(&self, path: &Path) -> Option<BookPaths> {
+        self.state
+            .book_paths(path)
+            .map_err(|err| warn!(?err, "Loading the book paths."))
+            .ok()
+    }
+
+    /// Start previewing the books in the workspace `folder`.
+    async fn add_books(&self, folder: &Path) {
+        let book_roots = block_n_yield(|| find_book_roots(folder)).await;
+        for book_root in book_roots {
+            self.add_book(book_root).await;
+        }
+    }
+
+    /// Start previewing the book at absolute `book_root`, unless already.
+    async fn add_book(&self, book_root: PathBuf) {
+        if self.previewers.read().unwrap().contains_key(&book_root) {
+            return;
+        }
+        info!(?book_root, "Adding book.");
+        let client = self.client.clone();
+        match BookPreviewer::spawn(client, self.state.clone(), book_root.clone()).await {
+            Ok(previewer) => {
+                self.previewers
+                    .write()
+                    .unwrap()
+                    .insert(book_root.clone(), previewer);
+                self.state.book_roots.write().unwrap().insert(book_root);
+            }
+            Err(err) => error!(?err, ?book_root, "Spawning the previewer."),
+        }
+    }
+
+    /// Make sure the book containing the file at `path` is previewed,
+    /// locating its root by the closest `book.toml` above the file.
+    async fn locate_book(&self, path: &Path) {
+        let Some(book_root) = block_n_yield(|| find_book_root(path)).await else {
+            return;
+        };
+        if self.state.book_root(path).as_ref() == Some(&book_root)
+            || self.previewers.read().unwrap().contains_key(&book_root)
+        {
+            return;
+        }
+        self.add_book(book_root).await;
+        self.publish_diagnostics().await;
+    }
+
+    /// Stop previewing the books in or containing the workspace `folder`.
+    async fn remove_books(&self, folder: &Path) {
+        let removed_previewers: Vec<_> = {
+            let mut book_roots = self.state.book_roots.write().unwrap();
+            let mut previewers = self.previewers.write().unwrap();
+            let mut build_errors = self.state.build_errors.write().unwrap();
+            let mut offsets = self.preview_port_offsets.write().unwrap();
+            book_roots
+                .extract_if(.., |book_root| {
+                    book_root.starts_with(folder) || folder.starts_with(book_root)
+                })
+                .filter_map(|book_root| {
+                    info!(?book_root, "Removing book.");
+                    build_errors.remove(&book_root);
+                    offsets.remove(&book_root);
+                    previewers.remove(&book_root)
+                })
+                .collect()
+        };
+        for previewer in removed_previewers {
+            previewer.shut_down().await;
+        }
+    }
+
+    /// The previewer of the book containing `path`.
+    fn previewer(&self, path: &Path) -> Option<ActorRef<Previewer>> {
+        let book_root = self.state.book_root(path)?;
+        let previewers = self.previewers.read().unwrap();
+        previewers
+            .get(&book_root)
+            .map(|book_previewer| book_previewer.previewer.clone())
+    }
+
+    /// Send `msg` to the previewer of the book containing `path`, if any.
+    async fn cast_to_previewer(&self, path: &Path, msg: PreviewInfo) {
+        match self.previewer(path) {
+            Some(previewer) => previewer.cast(msg).await.expect("Previewer died."),
+            None => debug!(?path, "No book contains the file."),
+        }
+    }
+
+    /// Resynchronize the open document at `path` if it fell out of sync,
+    /// from the saved `text` if provided, or otherwise from the disk.
+    fn resync_document(&self, path: &Path, text: Option<String>) {
+        let mut documents = self.state.documents.write().unwrap();
+        let Some(document) = documents.get_mut(path).filter(|doc| doc.out_of_sync) else {
+            return;
+        };
+        match text.map_or_else(|| fs::read_to_string(path), Ok) {
+            Ok(text) => {
+                document.resync(text);
+                info!(?path, document.version, "Resynchronized document.");
+            }
+            Err(err) => error!(?err, ?path, "Reading the document to resynchronize"),
+        }
+    }
+
+    /// Root of the book containing `path` if provided,
+    /// or otherwise the first book.
+    fn pick_book_root(&self, path: Option<&Path>) -> Option<PathBuf> {
+        match path {
+            Some(path) => self.state.book_root(path),
+            None => self.state.book_roots.read().unwrap().first().cloned(),
+        }
+    }
+
+    fn all_previewers(&self) -> Vec<(PathBuf, ActorRef<Previewer>)> {
+        let previewers = self.previewers.read().unwrap();
+        previewers
+            .iter()
+            .map(|(book_root, book_previewer)| {
+                (book_root.clone(), book_previewer.previewer.clone())
+            })
+            .collect()
+    }
 }
 
 const OPEN_PREVIEW: &str = "open_preview";
 const STOP_PREVIEW: &str = "stop_preview";
+const FIND_UNRENDERED: &str = "find_unrendered";
+const SCROLL_PREVIEW: &str = "scroll_preview";
 
 #[tower_lsp::async_trait]
 impl LanguageServer for MDBookLS {
     async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
-        let book_root: PathBuf = params
-            .workspace_folders
-            .and_then(|folders| {
-                if folders.len() > 1 {
-                    warn!(
-                        ?folders,
-                        "More than one workspace folder detected. Only using the first one."
-                    );
-                }
-                folders.into_iter().next()
-            })
-            .map_or_else(|| ".".into(), |folder| folder.name.into());
-        debug!(?book_root, "Initializing server.");
-        self.live_patcher
-            .cast(PreviewInfo::BookRoot(book_root))
-            .await
-            .expect("Live patcher died.");
+        #[allow(deprecated)]
+        let folder_uris = match (params.workspace_folders, params.root_uri) {
+            (Some(folders), _) => folders.into_iter().map(|folder| folder.uri).collect(),
+            (None, Some(root_uri)) => vec![root_uri],
+            (None, None) => vec![],
+        };
+        let mut folders: Vec<PathBuf> = folder_uris.iter().filter_map(uri2abs_file_path).collect();
+        if folders.is_empty() {
+            folders.push(absolute_path(Path::new(".")).expect("Getting the current directory."));
+        }
+        let settings = Settings::parse(params.initialization_options.unwrap_or_default())
+            .map_err(|err| warn!(?err, "Using the default settings."))
+            .unwrap_or_default();
+        self.apply_settings(settings).await;
+        *self.state.client_capabilities.write().unwrap() = params.capabilities;
+        debug!(?folders, "Initializing server.");
+        for folder in folders {
+            self.add_books(&folder).await;
+        }
 
         Ok(InitializeResult {
             capabilities: server_capabilities(),
@@ -52,20 +297,54 @@ impl LanguageServer for MDBookLS {
         })
     }
 
+    async fn initialized(&self, _: InitializedParams) {
+        self.watch_files().await;
+        self.publish_diagnostics().await;
+    }
+
     async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
         match params.command.as_str() {
-            "open_preview" => {
-                let open_msg = open_params(params);
-                self.live_patcher
-                    .cast(open_msg)
-                    .await
-                    .expect("Live patcher died.");
+            OPEN_PREVIEW => {
+                let (socket_address, open_browser_at) = open_params(params);
+                let url = self.open_preview(socket_address, open_browser_at).await;
+                return Ok(url.map(Value::String));
+            }
+            STOP_PREVIEW => {
+                for (_, previewer) in self.all_previewers() {
+                    let msg = PreviewInfo::StopPreview;
+                    previewer.cast(msg).await.expect("Previewer died.");
+                }
+                self.preview_port_offsets.write().unwrap().clear();
+            }
+            FIND_UNRENDERED => {
+                let path = params.arguments.first().and_then(Value::as_str);
+                let unrendered = block_n_yield(|| {
+                    let book_root = self.pick_book_root(path.map(Path::new))?;
+                    let documents = self.state.documents.read().unwrap();
+                    let book = BookStructure::load(&book_root, &documents)
+                        .map_err(|err| warn!(?err, "Loading the book structure."))
+                        .ok()?;
+                    let files = self
+                        .state
+                        .source_files(&book_root, &book.src_dir, &documents);
+                    Some(unrendered_json(&book, &files, &documents))
+                })
+                .await;
+                return Ok(unrendered);
             }
-            "stop_preview" => self
-                .live_patcher
-                .cast(PreviewInfo::StopPreview)
-                .await
-                .expect("Live patcher died."),
+            SCROLL_PREVIEW => match scroll_params(params) {
+                Some((path, line)) => {
+                    self.cast_to_previewer(
+                        &path,
+                        PreviewInfo::ScrollTo {
+                            path: path.clone(),
+                            line,
+                        },
+                    )
+                    .await
+                }
+                None => warn!("Ignoring `scroll_preview` without a path and a line."),
+            },
             unknown_command => {
                 error!(?unknown_command, "Requested to execute");
                 let message = format!("Unknown command `{unknown_command}`.");
@@ -83,17 +362,32 @@ impl LanguageServer for MDBookLS {
                     uri,
                     language_id,
                     version,
-                    text: _,
+                    text,
                 },
         }: DidOpenTextDocumentParams,
     ) {
         info!(uri.path = uri.path(), language_id, version, "did_open");
         match (language_id.as_str(), uri2abs_file_path(&uri)) {
             ("markdown", Some(path)) => {
-                let path = path.into();
-                let msg = PreviewInfo::Opened { path, version };
-                let task = self.live_patcher.cast(msg);
-                task.await.expect("LivePatcher died.");
+                self.locate_book(&path).await;
+                let document = Document::new(uri.clone(), version, text);
+                self.state
+                    .documents
+                    .write()
+                    .unwrap()
+                    .insert(path.clone(), document);
+                // NOTE: Open documents count as files even if not saved.
+                self.state.invalidate_book(&path);
+                let msg = PreviewInfo::Opened {
+                    path: path.clone(),
+                    version,
+                };
+                self.cast_to_previewer(&path, msg).await;
+                let auto_start_preview = self.state.settings.read().unwrap().auto_start_preview;
+                if auto_start_preview && self.preview_port_offsets.read().unwrap().is_empty() {
+                    self.open_preview(None, Some(path)).await;
+                }
+                self.publish_diagnostics().await;
             }
             ("markdown", _) => info!(uri.path = uri.path(), "Markdown but not a file!"),
             _ => {}
@@ -104,31 +398,70 @@ impl LanguageServer for MDBookLS {
         &self,
         DidChangeTextDocumentParams {
             text_document: VersionedTextDocumentIdentifier { uri, version },
-            mut content_changes,
+            content_changes,
         }: DidChangeTextDocumentParams,
     ) {
         info!(uri.path = uri.path(), version, "did_change");
-        match (content_changes.pop(), uri2abs_file_path(&uri)) {
-            (Some(TextDocumentContentChangeEvent { text, .. }), Some(path)) => {
-                let msg = PreviewInfo::ModifiedContent {
-                    path: path.into(),
-                    version,
-                    content: text,
-                };
-                let task = self.live_patcher.cast(msg);
-                task.await.expect("LivePatcher died.");
+        let Some(ref path) = uri2abs_file_path(&uri) else {
+            info!(uri.path = uri.path(), "Not a file!");
+            return;
+        };
+        let update = match self.state.documents.write().unwrap().get_mut(path) {
+            Some(document) => document
+                .update(version, content_changes)
+                .map(|changed| changed.then(|| (document.version, document.source.text.clone()))),
+            None => {
+                warn!(uri.path = uri.path(), "Changed but not opened!");
+                return;
+            }
+        };
+        let (version, content) = match update {
+            Ok(Some(version_n_content)) => version_n_content,
+            Ok(None) => return,
+            Err(err) => {
+                error!(?err, "Document out of sync");
+                let message = format!("{err:#}");
+                self.client.show_message(MessageType::ERROR, message).await;
+                return;
+            }
+        };
+        let msg = PreviewInfo::ModifiedContent {
+            path: path.into(),
+            version,
+            content,
+        };
+        self.cast_to_previewer(path, msg).await;
+        match self.state.is_summary_md(path) {
+            true => {
+                self.state.invalidate_summary(path);
+                self.publish_diagnostics().await;
+            }
+            false => {
+                self.state.invalidate_chapter(path);
+                self.state
+                    .publish_document_diagnostics(&self.client, path)
+                    .await
             }
-            (Some(_), _) => info!(uri.path = uri.path(), "Not a file!"),
-            _ => warn!("Empty content change!"),
         }
-        if !content_changes.is_empty() {
-            warn!(
-                ?content_changes,
-                "Unexpected due to `TextDocumentSyncKind::FULL`: more than one content changes! Only handled the last one."
-            );
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        info!(uri.path = params.text_document.uri.path(), "did_save");
+        if let Some(ref path) = uri2abs_file_path(&params.text_document.uri) {
+            self.resync_document(path, params.text);
+            self.state.invalidate_book(path);
+            self.publish_diagnostics().await;
+            let msg = PreviewInfo::Saved(path.into());
+            self.cast_to_previewer(path, msg).await;
         }
     }
 
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        debug!(?params.changes, "did_change_watched_files");
+        self.state.invalidate_books();
+        self.publish_diagnostics().await;
+    }
+
     async fn did_close(
         &self,
         DidCloseTextDocumentParams {
@@ -136,20 +469,277 @@ impl LanguageServer for MDBookLS {
         }: DidCloseTextDocumentParams,
     ) {
         info!(uri.path = uri.path(), "did_close");
-        if let Some(path) = uri2abs_file_path(&uri) {
+        if let Some(ref path) = uri2abs_file_path(&uri) {
+            self.state.documents.write().unwrap().remove(path);
+            self.state.invalidate_book(path);
             let msg = PreviewInfo::Closed(path.into());
-            let task = self.live_patcher.cast(msg);
-            task.await.expect("LivePatcher died.");
+            self.cast_to_previewer(path, msg).await;
+            // Clear the diagnostics of the closed chapter.
+            self.client.publish_diagnostics(uri, vec![], None).await;
+            self.publish_diagnostics().await;
+        }
+    }
+
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        let WorkspaceFoldersChangeEvent { added, removed } = params.event;
+        info!(?added, ?removed, "did_change_workspace_folders");
+        for folder in removed {
+            if let Some(ref folder) = uri2abs_file_path(&folder.uri) {
+                self.remove_books(folder).await;
+            }
         }
+        for folder in added {
+            if let Some(ref folder) = uri2abs_file_path(&folder.uri) {
+                self.add_books(folder).await;
+            }
+        }
+        self.state.invalidate_books();
+        self.publish_diagnostics().await;
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        info!(?params.settings, "did_change_configuration");
+        // NOTE: Clients using the pull model send `null`.
+        if params.settings.is_null() {
+            return;
+        }
+        match Settings::parse(params.settings) {
+            Ok(settings) => {
+                self.apply_settings(settings).await;
+                self.state.invalidate_books();
+                self.publish_diagnostics().await;
+            }
+            Err(err) => {
+                warn!(?err, "Ignoring invalid settings.");
+                let message = format!("Invalid mdBook-LS settings: {err:#}");
+                self.client
+                    .show_message(MessageType::WARNING, message)
+                    .await;
+            }
+        }
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let uri = params.text_document.uri;
+        let Some(ref path) = uri2abs_file_path(&uri) else {
+            return Ok(None);
+        };
+        let documents = self.state.documents.read().unwrap();
+        let Some(source) = read_source(&documents, path) else {
+            return Ok(None);
+        };
+        let symbols = match path.file_name().is_some_and(|name| name == "SUMMARY.md") {
+            true => summary_symbols(&source),
+            false => chapter_symbols(path, &source, &documents),
+        };
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let Some(ref path) = uri2abs_file_path(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        let Some(paths) = self.book_paths(path) else {
+            return Ok(None);
+        };
+        let can_create_files = self.state.can_create_files();
+        let actions = block_n_yield(|| {
+            let documents = self.state.documents.read().unwrap();
+            let source = read_source(&documents, path)?;
+            let offset = source.offset(params.range.start);
+            let actions = code_actions(&paths, path, &source, offset, &documents, can_create_files);
+            Some(actions)
+        })
+        .await;
+        Ok(actions)
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position;
+        let Some(ref path) = uri2abs_file_path(&text_document.uri) else {
+            return Ok(None);
+        };
+        let Some(paths) = self.book_paths(path) else {
+            return Ok(None);
+        };
+        let items = block_n_yield(|| {
+            let documents = self.state.documents.read().unwrap();
+            let source = read_source(&documents, path)?;
+            let offset = source.offset(position);
+            Some(complete(&paths, path, &source, offset, &documents))
+        })
+        .await;
+        Ok(items.map(CompletionResponse::Array))
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let Some(ref path) = uri2abs_file_path(&text_document.uri) else {
+            return Ok(None);
+        };
+        let Some(paths) = self.book_paths(path) else {
+            return Ok(None);
+        };
+        let location = block_n_yield(|| {
+            let documents = self.state.documents.read().unwrap();
+            let source = read_source(&documents, path)?;
+            let offset = source.offset(position);
+            find_definition(&paths, path, &source, offset, &documents)
+        })
+        .await;
+        Ok(location.map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let Some(ref path) = uri2abs_file_path(&text_document.uri) else {
+            return Ok(None);
+        };
+        let Some(paths) = self.book_paths(path) else {
+            return Ok(None);
+        };
+        let hover = block_n_yield(|| {
+            let documents = self.state.documents.read().unwrap();
+            let source = read_source(&documents, path)?;
+            let offset = source.offset(position);
+            hover(&paths, path, &source, offset, &documents)
+        })
+        .await;
+        Ok(hover)
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position;
+        let Some(ref path) = uri2abs_file_path(&text_document.uri) else {
+            return Ok(None);
+        };
+        let Some(paths) = self.book_paths(path) else {
+            return Ok(None);
+        };
+        let include_declaration = params.context.include_declaration;
+        let locations = block_n_yield(|| {
+            let documents = self.state.documents.read().unwrap();
+            let source = read_source(&documents, path)?;
+            let offset = source.offset(position);
+            Some(reference_locations(
+                &paths,
+                path,
+                &source,
+                offset,
+                include_declaration,
+                &documents,
+            ))
+        })
+        .await;
+        Ok(locations)
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let Some(ref path) = uri2abs_file_path(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        let documents = self.state.documents.read().unwrap();
+        let Some(source) = read_source(&documents, path) else {
+            return Ok(None);
+        };
+        let offset = source.offset(params.position);
+        Ok(prepare_heading_rename(path, &source, offset, &documents))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position;
+        let Some(ref path) = uri2abs_file_path(&text_document.uri) else {
+            return Ok(None);
+        };
+        let Some(paths) = self.book_paths(path) else {
+            return Ok(None);
+        };
+        let edit = block_n_yield(|| {
+            let documents = self.state.documents.read().unwrap();
+            let source = read_source(&documents, path)?;
+            let offset = source.offset(position);
+            rename_heading(&paths, path, &source, offset, &params.new_name, &documents)
+        })
+        .await;
+        Ok(edit)
+    }
+
+    async fn will_rename_files(&self, params: RenameFilesParams) -> Result<Option<WorkspaceEdit>> {
+        let mut renames_by_book: HashMap<PathBuf, HashMap<_, _>> = HashMap::new();
+        for FileRename { old_uri, new_uri } in &params.files {
+            let (Ok(old_uri), Ok(new_uri)) = (old_uri.parse(), new_uri.parse()) else {
+                continue;
+            };
+            let (Some(old_path), Some(new_path)) =
+                (uri2abs_file_path(&old_uri), uri2abs_file_path(&new_uri))
+            else {
+                continue;
+            };
+            if let Some(book_root) = self.state.book_root(&old_path) {
+                let renames = renames_by_book.entry(book_root).or_default();
+                renames.insert(old_path, new_path);
+            }
+        }
+        let edit = block_n_yield(|| {
+            let documents = self.state.documents.read().unwrap();
+            let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+            for (book_root, renames) in renames_by_book {
+                let Some(paths) = self.book_paths(&book_root) else {
+                    continue;
+                };
+                let edit = rename_files(&paths, &renames, &documents);
+                for (uri, edits) in edit.changes.into_iter().flatten() {
+                    changes.entry(uri).or_default().extend(edits);
+                }
+            }
+            WorkspaceEdit::new(changes)
+        })
+        .await;
+        Ok(Some(edit))
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        let symbols = block_n_yield(|| self.state.workspace_symbols(&params.query)).await;
+        Ok(Some(symbols))
     }
 
     async fn shutdown(&self) -> Result<()> {
-        self.live_patcher.cancel();
+        for (_, previewer) in self.all_previewers() {
+            previewer.cancel();
+        }
         Ok(())
     }
 }
 
-fn open_params(params: ExecuteCommandParams) -> PreviewInfo {
+/// The socket address and the chapter path to open the browser at.
+fn open_params(params: ExecuteCommandParams) -> (Option<SocketAddr>, Option<PathBuf>) {
     let mut args = params.arguments.into_iter();
     let socket_address = args.next().and_then(|v| {
         v.as_str().and_then(|s| {
@@ -159,29 +749,106 @@ fn open_params(params: ExecuteCommandParams) -> PreviewInfo {
         })
     });
     let open_browser_at = args.next().and_then(|v| v.as_str().map(PathBuf::from));
-    PreviewInfo::OpenPreview {
-        socket_address,
-        open_browser_at,
-    }
+    (socket_address, open_browser_at)
+}
+
+/// Absolute chapter path and zero-based line to scroll the preview to.
+fn scroll_params(params: ExecuteCommandParams) -> Option<(PathBuf, usize)> {
+    let mut args = params.arguments.into_iter();
+    let path = args.next()?.as_str().map(PathBuf::from)?;
+    let line = args.next()?.as_u64()?;
+    Some((path, line as usize))
 }
 
 impl Drop for MDBookLS {
     fn drop(&mut self) {
-        self.live_patcher_handle.abort();
+        for book_previewer in self.previewers.read().unwrap().values() {
+            book_previewer.abort();
+        }
     }
 }
 
-fn uri2abs_file_path(uri: &Url) -> Option<&Path> {
-    (uri.scheme() == "file").then_some(Path::new(uri.path()))
+/// The [`Unrendered`] chapters as JSON,
+/// with paths relative to the source directory.
+fn unrendered_json(book: &BookStructure, files: &SourceFiles, documents: &Documents) -> Value {
+    let Unrendered { orphans, drafts } = Unrendered::find(book, files, documents);
+    let orphans: Vec<_> = orphans
+        .iter()
+        .map(|path| path.strip_prefix(&book.src_dir).unwrap_or(path))
+        .collect();
+    let summary = read_source(documents, &book.summary_md);
+    let drafts: Vec<_> = drafts
+        .into_iter()
+        .map(|draft| {
+            let line = summary
+                .as_ref()
+                .map(|summary| summary.position(draft.range.start).line + 1);
+            json!({ "name": draft.name, "line": line })
+        })
+        .collect();
+    json!({ "orphans": orphans, "drafts": drafts })
+}
+
+/// The absolute file path `uri` points to, percent-decoded.
+fn uri2abs_file_path(uri: &Url) -> Option<PathBuf> {
+    uri.to_file_path()
+        .map_err(|()| debug!(%uri, "Not a file URI."))
+        .ok()
 }
 
 fn server_capabilities() -> ServerCapabilities {
     ServerCapabilities {
-        // NOTE: Let the client send the whole file on every change so
-        // we do not need to patch it ourselves.
-        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        text_document_sync: Some(TextDocumentSyncCapability::Options(
+            TextDocumentSyncOptions {
+                open_close: Some(true),
+                change: Some(TextDocumentSyncKind::INCREMENTAL),
+                save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                ..Default::default()
+            },
+        )),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+            code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+            ..Default::default()
+        })),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(["(", "/", "#", ":"].map(Into::into).into()),
+            ..Default::default()
+        }),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        references_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Right(RenameOptions {
+            prepare_provider: Some(true),
+            work_done_progress_options: Default::default(),
+        })),
+        workspace: Some(WorkspaceServerCapabilities {
+            workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                supported: Some(true),
+                change_notifications: Some(OneOf::Left(true)),
+            }),
+            file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                will_rename: Some(FileOperationRegistrationOptions {
+                    filters: vec![FileOperationFilter {
+                        scheme: Some("file".into()),
+                        pattern: FileOperationPattern {
+                            glob: "**/*.md".into(),
+                            matches: Some(FileOperationPatternKind::File),
+                            options: None,
+                        },
+                    }],
+                }),
+                ..Default::default()
+            }),
+        }),
+        workspace_symbol_provider: Some(OneOf::Left(true)),
         execute_command_provider: Some(ExecuteCommandOptions {
-            commands: vec![OPEN_PREVIEW.into(), STOP_PREVIEW.into()],
+            commands: vec![
+                OPEN_PREVIEW.into(),
+                STOP_PREVIEW.into(),
+                FIND_UNRENDERED.into(),
+                SCROLL_PREVIEW.into(),
+            ],
             work_done_progress_options: Default::default(),
         }),
         ..Default::default()
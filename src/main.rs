@@ -4,18 +4,22 @@ use anyhow::Result;
 use clap::Parser;
 use mdbook_ls::run_mdbook_ls;
 use tracing::*;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{fmt, prelude::*, reload, EnvFilter};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_ansi(false)
-        .with_writer(stderr)
-        .with_env_filter(EnvFilter::from_default_env())
+    let (env_filter, reload_handle) = reload::Layer::new(EnvFilter::from_default_env());
+    tracing_subscriber::registry()
+        .with(env_filter)
+        .with(fmt::layer().with_ansi(false).with_writer(stderr))
         .init();
     let app = App::parse();
     debug!(?app);
-    run_mdbook_ls().await
+    let set_log_filter = move |directives: &str| {
+        reload_handle.reload(EnvFilter::try_new(directives)?)?;
+        Ok(())
+    };
+    run_mdbook_ls(Box::new(set_log_filter)).await
 }
 
 #[derive(Clone, Debug, Parser)]
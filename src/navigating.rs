@@ -0,0 +1,274 @@
+use super::*;
+
+/// Where the `SUMMARY.md` entry, include directive or link at `offset` in
+/// the file at absolute `path` points to.
+pub fn find_definition(
+    paths: &BookPaths,
+    path: &Path,
+    source: &SourceText,
+    offset: usize,
+    documents: &Documents,
+) -> Option<Location> {
+    if path == paths.summary_md {
+        let entry = parse_summary_entries(&source.text)
+            .into_iter()
+            .find(|entry| entry.range.contains(&offset))?;
+        let (chapter, _) = split_link_dest(&entry.location)?;
+        let chapter_path = paths.src_dir.join(decode_summary_path(chapter));
+        return (!chapter.is_empty())
+            .then(|| file_location(&chapter_path, Position::default(), documents))?;
+    }
+
+    let maybe_include = parse_includes(&source.text)
+        .into_iter()
+        .find(|include| include.range.contains(&offset));
+    if let Some(include) = maybe_include {
+        let included_path = resolve_relative(path, &include.path);
+        let line = match include.lines {
+            LineSelector::Anchor(anchor) => {
+                let included = read_source(documents, &included_path)?;
+                let anchors = anchors(&included.text);
+                let maybe_line = anchors.into_iter().find(|(name, _)| *name == anchor);
+                maybe_line.map_or(0, |(_, line)| line)
+            }
+            LineSelector::Range { start, .. } => start,
+        };
+        return file_location(&included_path, Position::new(line as u32, 0), documents);
+    }
+
+    let link = parse_chapter(&source.text)
+        .links
+        .into_iter()
+        .find(|link| link.range.contains(&offset))?;
+    let (link_path, _) = link.path_n_fragment()?;
+    let fragment = link.fragment.as_deref();
+    if (link_path, fragment) == ("", None) {
+        return None;
+    }
+    let target = link_target(path, link_path);
+    let position = match fragment {
+        Some(fragment) => {
+            let target_source = read_source(documents, &target)?;
+            anchor_position(&target, &target_source, fragment, documents).unwrap_or_default()
+        }
+        None => Position::default(),
+    };
+    file_location(&target, position, documents)
+}
+
+/// Locations of the links and include directives in the book that point to
+/// the heading at `offset` in the chapter at absolute `path`,
+/// or to the chapter if `offset` is not in a heading.
+pub fn reference_locations(
+    paths: &BookPaths,
+    path: &Path,
+    source: &SourceText,
+    offset: usize,
+    include_declaration: bool,
+    documents: &Documents,
+) -> Vec<Location> {
+    let heading = chapter_headings(path, &source.text, documents)
+        .into_iter()
+        .find(|heading| heading.range.contains(&offset));
+    let mut locations = Vec::new();
+    if let (true, Some(heading), Ok(uri)) =
+        (include_declaration, &heading, Url::from_file_path(path))
+    {
+        locations.push(Location::new(uri, source.range(&heading.range)));
+    }
+    let fragment = heading.as_ref().map(|heading| heading.id.as_str());
+    for references in find_references(&paths.src_dir, path, fragment, documents) {
+        let Ok(uri) = Url::from_file_path(&references.path) else {
+            continue;
+        };
+        let link_ranges = references.links.iter().map(|link| &link.range);
+        let include_ranges = references.includes.iter().map(|include| &include.range);
+        locations.extend(
+            link_ranges
+                .chain(include_ranges)
+                .map(|range| Location::new(uri.clone(), references.source.range(range))),
+        );
+    }
+    locations
+}
+
+/// The links and include directives in a file that point to
+/// a chapter or a heading.
+#[derive(Clone, Debug)]
+pub struct FileReferences {
+    pub path: PathBuf,
+    pub source: SourceText,
+    pub links: Vec<MdLink>,
+    pub includes: Vec<IncludeDirective>,
+}
+
+/// References in the Markdown files under `src_dir` to the file at
+/// absolute `target`, or only to its heading with ID `fragment` if provided.
+pub fn find_references(
+    src_dir: &Path,
+    target: &Path,
+    fragment: Option<&str>,
+    documents: &Documents,
+) -> Vec<FileReferences> {
+    markdown_files(src_dir, documents)
+        .into_iter()
+        .filter_map(|path| {
+            let source = read_source(documents, &path)?;
+            let links: Vec<_> = parse_chapter(&source.text)
+                .links
+                .into_iter()
+                .filter(|link| {
+                    let Some((link_path, _)) = link.path_n_fragment() else {
+                        return false;
+                    };
+                    match fragment {
+                        // Links within the chapter do not depend on it.
+                        None if link_path.is_empty() => false,
+                        None => link_target(&path, link_path) == target,
+                        Some(fragment) => {
+                            link.fragment.as_deref() == Some(fragment)
+                                && link_target(&path, link_path) == target
+                        }
+                    }
+                })
+                .collect();
+            let includes: Vec<_> = match fragment {
+                Some(_) => vec![],
+                None => parse_includes(&source.text)
+                    .into_iter()
+                    .filter(|include| resolve_relative(&path, &include.path) == target)
+                    .collect(),
+            };
+            (!links.is_empty() || !includes.is_empty()).then_some(FileReferences {
+                path,
+                source,
+                links,
+                includes,
+            })
+        })
+        .collect()
+}
+
+/// Absolute path of the file a link with `link_path` in the file at
+/// absolute `path` points to.
+pub fn link_target(path: &Path, link_path: &str) -> PathBuf {
+    match link_path {
+        "" => path.to_owned(),
+        _ => resolve_relative(path, &decode_link_part(link_path)),
+    }
+}
+
+/// Position of the heading with ID `id` in the chapter at absolute `path`
+/// with `source`, or of the raw HTML element with the `id` attribute.
+fn anchor_position(
+    path: &Path,
+    source: &SourceText,
+    id: &str,
+    documents: &Documents,
+) -> Option<Position> {
+    let heading_start = chapter_headings(path, &source.text, documents)
+        .into_iter()
+        .find(|heading| heading.id == id)
+        .map(|heading| heading.range.start);
+    let start = heading_start.or_else(|| source.text.find(&format!("id=\"{id}\"")))?;
+    Some(source.position(start))
+}
+
+/// Location at `position` in the file at absolute `path`, if it exists.
+fn file_location(path: &Path, position: Position, documents: &Documents) -> Option<Location> {
+    if !path.exists() && !documents.contains_key(path) {
+        return None;
+    }
+    let uri = Url::from_file_path(path).ok()?;
+    Some(Location::new(
+        uri,
+        lsp_types::Range::new(position, position),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn documents() -> Documents {
+        open_documents([
+            (
+                "/book/src/SUMMARY.md",
+                "# Summary\n\n- [One](one.md)\n- [Space](sp%20ace.md)\n",
+            ),
+            ("/book/src/sp ace.md", "# Space\n"),
+            (
+                "/book/src/one.md",
+                "# One\n\n[Two](two.md#caf%C3%A9) [Whole](two.md)\n\n{{#include code.rs:main}}\n",
+            ),
+            ("/book/src/two.md", "Intro.\n\n# Café\n\n[Back](one.md)\n"),
+            (
+                "/book/src/code.rs",
+                "use a;\n// ANCHOR: main\nfn main() {}\n",
+            ),
+        ])
+    }
+
+    fn definition(path: &str, needle: &str) -> Option<(String, u32)> {
+        let documents = documents();
+        let location = at_needle(&documents, path, needle, |paths, path, source, offset| {
+            find_definition(paths, path, source, offset, &documents)
+        })?;
+        let target = location.uri.to_file_path().unwrap();
+        Some((target.display().to_string(), location.range.start.line))
+    }
+
+    #[test]
+    fn find_definitions() {
+        let one = Some(("/book/src/one.md".into(), 0));
+        assert_eq!(definition("/book/src/SUMMARY.md", "One"), one);
+        let space = Some(("/book/src/sp ace.md".into(), 0));
+        assert_eq!(definition("/book/src/SUMMARY.md", "Space"), space);
+        assert_eq!(definition("/book/src/SUMMARY.md", "# Summary"), None);
+        let cafe = Some(("/book/src/two.md".into(), 2));
+        assert_eq!(definition("/book/src/one.md", "[Two]"), cafe);
+        let two = Some(("/book/src/two.md".into(), 0));
+        assert_eq!(definition("/book/src/one.md", "[Whole]"), two);
+        let main = Some(("/book/src/code.rs".into(), 1));
+        assert_eq!(definition("/book/src/one.md", "{{#include"), main);
+        assert_eq!(definition("/book/src/one.md", "# One"), None);
+    }
+
+    fn references(path: &str, needle: &str) -> Vec<(String, String)> {
+        let documents = documents();
+        let locations = at_needle(&documents, path, needle, |paths, path, source, offset| {
+            reference_locations(paths, path, source, offset, false, &documents)
+        });
+        let mut references: Vec<_> = locations
+            .into_iter()
+            .map(|location| {
+                let path = location.uri.to_file_path().unwrap();
+                let source = read_source(&documents, &path).unwrap();
+                let range = source.offset(location.range.start)..source.offset(location.range.end);
+                (path.display().to_string(), source.text[range].to_owned())
+            })
+            .collect();
+        references.sort();
+        references
+    }
+
+    #[test]
+    fn find_heading_and_chapter_references() {
+        let to = |path: &str, text: &str| (path.to_owned(), text.to_owned());
+        assert_eq!(
+            references("/book/src/two.md", "# Café"),
+            [to("/book/src/one.md", "[Two](two.md#caf%C3%A9)")]
+        );
+        assert_eq!(
+            references("/book/src/two.md", "Intro"),
+            [
+                to("/book/src/one.md", "[Two](two.md#caf%C3%A9)"),
+                to("/book/src/one.md", "[Whole](two.md)"),
+            ]
+        );
+        assert_eq!(
+            references("/book/src/code.rs", "use"),
+            [to("/book/src/one.md", "{{#include code.rs:main}}")]
+        );
+    }
+}
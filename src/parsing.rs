@@ -0,0 +1,400 @@
+use super::*;
+
+/// The parts of a chapter's Markdown source that the language server
+/// cares about, with byte ranges into the source.
+#[derive(Clone, Debug, Default)]
+pub struct ParsedChapter {
+    pub headings: Vec<Heading>,
+    pub links: Vec<MdLink>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Heading {
+    /// 1 for `#`, 2 for `##`, etc.
+    pub level: usize,
+    /// Plain text of the heading.
+    pub title: String,
+    /// The anchor ID mdBook assigns to the heading.
+    pub id: String,
+    pub range: Range<usize>,
+    /// Range of the heading's text in the source, [`None`] if empty.
+    pub content_range: Option<Range<usize>>,
+}
+
+/// A Markdown link or image.
+#[derive(Clone, Debug)]
+pub struct MdLink {
+    pub dest: String,
+    /// Range of the destination in the source,
+    /// or of the whole link if the destination is elsewhere,
+    /// e.g., for reference links.
+    pub dest_range: Range<usize>,
+    pub range: Range<usize>,
+    /// The percent-decoded fragment of the destination,
+    /// [`None`] if empty or if the link points outside the book.
+    pub fragment: Option<String>,
+}
+
+/// A heading being parsed.
+struct HeadingBuilder<'a> {
+    level: usize,
+    explicit_id: Option<String>,
+    range: Range<usize>,
+    content_range: Option<Range<usize>>,
+    events: Vec<Event<'a>>,
+}
+
+/// Parse `text` the same way mdBook's HTML renderer does.
+pub fn parse_chapter(text: &str) -> ParsedChapter {
+    let mut parsed = ParsedChapter::default();
+    let mut id_counter = HashMap::new();
+    let mut heading: Option<HeadingBuilder> = None;
+    for (event, range) in utils::new_cmark_parser(text, false).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, id, .. }) => {
+                heading = Some(HeadingBuilder {
+                    level: level as usize,
+                    explicit_id: id.map(|id| id.to_string()),
+                    range,
+                    content_range: None,
+                    events: vec![],
+                });
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(h) = heading.take() {
+                    parsed.headings.push(h.build(&mut id_counter));
+                }
+            }
+            event => {
+                if let Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) =
+                    &event
+                {
+                    parsed
+                        .links
+                        .push(MdLink::new(text, dest_url, range.clone()));
+                }
+                if let Some(h) = &mut heading {
+                    h.events.push(event);
+                    h.content_range = Some(match &h.content_range {
+                        Some(content_range) => content_range.start..range.end,
+                        None => range,
+                    });
+                }
+            }
+        }
+    }
+    parsed
+}
+
+impl HeadingBuilder<'_> {
+    fn build(self, id_counter: &mut HashMap<String, usize>) -> Heading {
+        let title = self
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+                _ => None,
+            })
+            .collect();
+        // NOTE: mdBook derives the ID from the rendered HTML of the heading.
+        let id = self.explicit_id.unwrap_or_else(|| {
+            let mut inner_html = String::new();
+            html::push_html(&mut inner_html, self.events.into_iter());
+            utils::unique_id_from_content(&inner_html, id_counter)
+        });
+        Heading {
+            level: self.level,
+            title,
+            id,
+            range: self.range,
+            content_range: self.content_range,
+        }
+    }
+}
+
+impl MdLink {
+    fn new(text: &str, dest: &str, range: Range<usize>) -> Self {
+        let source = &text[range.clone()];
+        let dest_range = match source.find("](") {
+            Some(start) if !dest.is_empty() => match source[start..].find(dest) {
+                Some(offset) => Some(start + offset..start + offset + dest.len()),
+                // The destination may have backslash escapes.
+                None => {
+                    let after_paren = &source[start + 2..];
+                    let offset = start + 2 + after_paren.len()
+                        - after_paren.trim_start().trim_start_matches('<').len();
+                    escaped_len(&source[offset..], dest).map(|len| offset..offset + len)
+                }
+            }
+            .map(|dest_range| range.start + dest_range.start..range.start + dest_range.end),
+            _ => None,
+        };
+        let fragment = split_link_dest(dest)
+            .and_then(|(_, fragment)| fragment)
+            .filter(|fragment| !fragment.is_empty())
+            .map(|fragment| decode_link_part(fragment).into_owned());
+        Self {
+            dest: dest.into(),
+            dest_range: dest_range.unwrap_or_else(|| range.clone()),
+            range,
+            fragment,
+        }
+    }
+
+    /// Range of the destination's path in `text`,
+    /// if the destination is written in the link.
+    pub fn path_range(&self, text: &str) -> Option<Range<usize>> {
+        let (path, _) = self.path_n_fragment()?;
+        self.source_range(text, 0..path.len())
+    }
+
+    /// Range of the destination's fragment in `text`,
+    /// if the destination is written in the link.
+    pub fn fragment_range(&self, text: &str) -> Option<Range<usize>> {
+        let fragment = self.path_n_fragment()?.1?;
+        let start = self.dest.rfind(fragment)?;
+        self.source_range(text, start..start + fragment.len())
+    }
+
+    /// Range in `text` of the `range` of the destination,
+    /// which may be written with backslash escapes.
+    fn source_range(&self, text: &str, range: Range<usize>) -> Option<Range<usize>> {
+        let written = text.get(self.dest_range.clone())?;
+        if escaped_len(written, &self.dest)? != written.len() {
+            return None;
+        }
+        let start = self.dest_range.start + escaped_len(written, &self.dest[..range.start])?;
+        let end = self.dest_range.start + escaped_len(written, &self.dest[..range.end])?;
+        Some(start..end)
+    }
+
+    /// The path and fragment of the destination,
+    /// or [`None`] if it points outside the book, e.g., a URL.
+    pub fn path_n_fragment(&self) -> Option<(&str, Option<&str>)> {
+        split_link_dest(&self.dest)
+    }
+}
+
+/// Length of `unescaped` as written at the start of `text`,
+/// where ASCII punctuation may be escaped with backslashes.
+fn escaped_len(text: &str, unescaped: &str) -> Option<usize> {
+    let mut len = 0;
+    for char in unescaped.chars() {
+        let rest = &text[len..];
+        if rest.starts_with(char) {
+            len += char.len_utf8();
+        } else if char.is_ascii_punctuation() && rest.strip_prefix('\\')?.starts_with(char) {
+            len += 2;
+        } else {
+            return None;
+        }
+    }
+    Some(len)
+}
+
+/// Split a relative link destination into its path and fragment;
+/// [`None`] for URLs and absolute paths.
+pub fn split_link_dest(dest: &str) -> Option<(&str, Option<&str>)> {
+    let is_url = dest
+        .split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.contains(['/', '#', '?']));
+    if is_url || dest.starts_with('/') {
+        return None;
+    }
+    let dest = dest.split_once('?').map_or(dest, |(dest, _)| dest);
+    Some(match dest.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (dest, None),
+    })
+}
+
+/// Percent-decode a link destination's path or fragment, as browsers do.
+pub fn decode_link_part(part: &str) -> Cow<'_, str> {
+    percent_decode_str(part).decode_utf8_lossy()
+}
+
+/// Characters to escape in a link path so Markdown and browsers keep it whole.
+const LINK_PATH_ESCAPES: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'(')
+    .add(b')')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`');
+
+/// Percent-encode a file path to write as a link destination's path.
+pub fn encode_link_path(path: &str) -> String {
+    utf8_percent_encode(path, LINK_PATH_ESCAPES).to_string()
+}
+
+/// Decode a `SUMMARY.md` link destination's path as mdBook does,
+/// which only decodes `%20`.
+pub fn decode_summary_path(path: &str) -> String {
+    path.replace("%20", " ")
+}
+
+/// Encode a file path to write as a `SUMMARY.md` link destination's path:
+/// spaces as `%20`, the only escape mdBook decodes there,
+/// and backslash escapes for the characters Markdown would cut the link at.
+pub fn encode_summary_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            ' ' => encoded.push_str("%20"),
+            '(' | ')' | '<' | '>' | '\\' => {
+                encoded.push('\\');
+                encoded.push(c);
+            }
+            _ => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+/// An entry of `SUMMARY.md`, with byte ranges into its source.
+#[derive(Clone, Debug)]
+pub struct SummaryEntry {
+    pub kind: SummaryEntryKind,
+    /// 0 for part titles and prefix/suffix chapters,
+    /// 1 for top-level numbered chapters under a part title,
+    /// 2 for their sub-chapters, etc.
+    /// Numbered chapters before any part title are one level higher,
+    /// so no prefix chapter contains them.
+    pub level: usize,
+    pub name: String,
+    /// Link destination relative to the source directory;
+    /// empty for drafts and part titles.
+    pub location: String,
+    /// Range of the link destination, or of the whole entry if none.
+    pub location_range: Range<usize>,
+    pub range: Range<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SummaryEntryKind {
+    PartTitle,
+    PrefixChapter,
+    /// With its section number, e.g., `[1, 2]` for `1.2.`.
+    NumberedChapter(Vec<u32>),
+    SuffixChapter,
+}
+
+impl SummaryEntry {
+    pub fn is_draft(&self) -> bool {
+        self.kind != SummaryEntryKind::PartTitle && self.location.is_empty()
+    }
+}
+
+/// Which section of `SUMMARY.md` is being parsed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SummarySection {
+    Prefix,
+    Numbered,
+    Suffix,
+}
+
+/// Parse the entries of `SUMMARY.md` in order, leniently,
+/// following the structure mdBook's summary parser expects.
+pub fn parse_summary_entries(text: &str) -> Vec<SummaryEntry> {
+    let mut entries = Vec::new();
+    let mut section = SummarySection::Prefix;
+    let mut list_depth = 0;
+    let mut section_number: Vec<u32> = vec![];
+    let mut in_part = false;
+    let mut may_be_title = true;
+    let mut in_title = false;
+    let mut entry: Option<SummaryEntry> = None;
+    for (event, range) in Parser::new(text).into_offset_iter() {
+        let first_event = may_be_title;
+        if !matches!(event, Event::Html(_) | Event::InlineHtml(_)) {
+            may_be_title = false;
+        }
+        match event {
+            Event::Start(Tag::Heading {
+                level: HeadingLevel::H1,
+                ..
+            }) if first_event => in_title = true,
+            Event::End(TagEnd::Heading(_)) if in_title => in_title = false,
+            Event::Start(Tag::Heading {
+                level: HeadingLevel::H1,
+                ..
+            }) => {
+                section = SummarySection::Numbered;
+                in_part = true;
+                entry = Some(SummaryEntry {
+                    kind: SummaryEntryKind::PartTitle,
+                    level: 0,
+                    name: String::new(),
+                    location: String::new(),
+                    location_range: range.clone(),
+                    range,
+                });
+            }
+            Event::Start(Tag::List(_)) => {
+                section = SummarySection::Numbered;
+                list_depth += 1;
+            }
+            Event::End(TagEnd::List(_)) => list_depth -= 1,
+            Event::Start(Tag::Link { dest_url, .. }) if entry.is_none() => {
+                let kind = match (list_depth, section) {
+                    (0, SummarySection::Prefix) => SummaryEntryKind::PrefixChapter,
+                    (0, _) => {
+                        section = SummarySection::Suffix;
+                        SummaryEntryKind::SuffixChapter
+                    }
+                    (depth, _) => {
+                        section_number.resize(depth, 0);
+                        *section_number.last_mut().unwrap() += 1;
+                        SummaryEntryKind::NumberedChapter(section_number.clone())
+                    }
+                };
+                let link = MdLink::new(text, &dest_url, range);
+                entry = Some(SummaryEntry {
+                    kind,
+                    level: list_depth - usize::from(list_depth > 0 && !in_part),
+                    name: String::new(),
+                    location: link.dest,
+                    location_range: link.dest_range,
+                    range: link.range,
+                });
+            }
+            Event::End(TagEnd::Heading(_) | TagEnd::Link) => entries.extend(entry.take()),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(entry) = &mut entry {
+                    entry.name.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_link_fragments() {
+        let text = "[a](ch%201.md#caf%C3%A9) [b](#) [c](https://a.b/#x) [d](#plain)";
+        let links = parse_chapter(text).links;
+        let fragments: Vec<_> = links.iter().map(|l| l.fragment.as_deref()).collect();
+        assert_eq!(fragments, [Some("café"), None, None, Some("plain")]);
+        // The raw fragment is still located in the source.
+        let range = links[0].fragment_range(text).unwrap();
+        assert_eq!(&text[range], "caf%C3%A9");
+    }
+
+    #[test]
+    fn encode_summary_paths_for_mdbook() {
+        let encoded = encode_summary_path("a dir/b (1).md");
+        assert_eq!(encoded, r"a%20dir/b%20\(1\).md");
+        let summary = format!("- [B]({encoded})\n");
+        let entries = parse_summary_entries(&summary);
+        assert_eq!(decode_summary_path(&entries[0].location), "a dir/b (1).md");
+    }
+}
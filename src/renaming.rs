@@ -0,0 +1,230 @@
+use super::*;
+
+/// Range and text of the heading at `offset` in the chapter at absolute
+/// `path` to rename. Headings from `{{#include}}`s cannot be renamed here.
+pub fn prepare_heading_rename(
+    path: &Path,
+    source: &SourceText,
+    offset: usize,
+    documents: &Documents,
+) -> Option<PrepareRenameResponse> {
+    let content_range = chapter_headings(path, &source.text, documents)
+        .into_iter()
+        .find(|heading| heading.range.contains(&offset))?
+        .content_range?;
+    Some(PrepareRenameResponse::RangeWithPlaceholder {
+        range: source.range(&content_range),
+        placeholder: source.text[content_range].into(),
+    })
+}
+
+/// Edits to rename the heading at `offset` in the chapter at absolute `path`
+/// to `new_name`, and to update the link fragments of
+/// the heading IDs that change as a result.
+pub fn rename_heading(
+    paths: &BookPaths,
+    path: &Path,
+    source: &SourceText,
+    offset: usize,
+    new_name: &str,
+    documents: &Documents,
+) -> Option<WorkspaceEdit> {
+    let headings = chapter_headings(path, &source.text, documents);
+    let content_range = headings
+        .iter()
+        .find(|heading| heading.range.contains(&offset))?
+        .content_range
+        .clone()?;
+    let mut new_text = source.text.clone();
+    new_text.replace_range(content_range.clone(), new_name);
+    // NOTE: Renaming a heading may change the IDs of
+    // the following headings with the same title.
+    let new_headings = chapter_headings(path, &new_text, documents);
+    let changed_ids = match headings.len() == new_headings.len() {
+        true => iter::zip(headings, new_headings)
+            .filter(|(old, new)| old.id != new.id)
+            .map(|(old, new)| (old.id, new.id))
+            .collect(),
+        false => {
+            warn!(
+                new_name,
+                "Renaming the heading changes the number of headings."
+            );
+            vec![]
+        }
+    };
+
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    let uri = Url::from_file_path(path).ok()?;
+    let edit = TextEdit::new(source.range(&content_range), new_name.into());
+    changes.entry(uri).or_default().push(edit);
+    for (old_id, new_id) in changed_ids {
+        for references in find_references(&paths.src_dir, path, Some(&old_id), documents) {
+            let Ok(uri) = Url::from_file_path(&references.path) else {
+                continue;
+            };
+            let source = &references.source;
+            let edits = references.links.iter().filter_map(|link| {
+                let range = link.fragment_range(&source.text)?;
+                Some(TextEdit::new(source.range(&range), new_id.clone()))
+            });
+            changes.entry(uri).or_default().extend(edits);
+        }
+    }
+    Some(WorkspaceEdit::new(changes))
+}
+
+/// Edits to update the link and include paths in the book
+/// when the files are moved from the old to the new absolute paths:
+/// those pointing to the moved files, and those in the moved files.
+pub fn rename_files(
+    paths: &BookPaths,
+    renames: &HashMap<PathBuf, PathBuf>,
+    documents: &Documents,
+) -> WorkspaceEdit {
+    let new_path = |path: PathBuf| renames.get(&path).cloned().unwrap_or(path);
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for (old_path, moved_path) in renames {
+        for references in find_references(&paths.src_dir, old_path, None, documents) {
+            // The links in moved files are handled below.
+            if renames.contains_key(&references.path) {
+                continue;
+            }
+            let Ok(uri) = Url::from_file_path(&references.path) else {
+                continue;
+            };
+            let source = &references.source;
+            let new_include_path = relative_link_path(&references.path, moved_path);
+            // NOTE: mdBook only decodes `%20` in `SUMMARY.md`.
+            let new_link_path = match references.path == paths.summary_md {
+                true => encode_summary_path(&new_include_path),
+                false => encode_link_path(&new_include_path),
+            };
+            let link_edits = references.links.iter().filter_map(|link| {
+                let range = link.path_range(&source.text)?;
+                Some(TextEdit::new(source.range(&range), new_link_path.clone()))
+            });
+            let include_edits = references.includes.iter().map(|include| {
+                TextEdit::new(source.range(&include.path_range), new_include_path.clone())
+            });
+            let edits = link_edits.chain(include_edits);
+            changes.entry(uri).or_default().extend(edits);
+        }
+
+        let (Some(source), Ok(uri)) = (
+            read_source(documents, old_path),
+            Url::from_file_path(old_path),
+        ) else {
+            continue;
+        };
+        let links = parse_chapter(&source.text).links;
+        let link_paths = links.iter().filter_map(|link| {
+            let (link_path, _) = link.path_n_fragment()?;
+            let range = link.path_range(&source.text)?;
+            (!link_path.is_empty()).then_some((decode_link_part(link_path), range, true))
+        });
+        let includes = parse_includes(&source.text);
+        let include_paths = includes.iter().map(|include| {
+            let path = Cow::Borrowed(include.path.as_str());
+            (path, include.path_range.clone(), false)
+        });
+        let edits = link_paths
+            .chain(include_paths)
+            .filter_map(|(link_path, range, is_link)| {
+                let target = new_path(resolve_relative(old_path, &link_path));
+                (resolve_relative(moved_path, &link_path) != target).then(|| {
+                    let new_link_path = relative_link_path(moved_path, &target);
+                    let new_link_path = match is_link {
+                        true => encode_link_path(&new_link_path),
+                        false => new_link_path,
+                    };
+                    TextEdit::new(source.range(&range), new_link_path)
+                })
+            });
+        changes.entry(uri).or_default().extend(edits);
+    }
+    changes.retain(|_, edits| !edits.is_empty());
+    WorkspaceEdit::new(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_texts(edit: &WorkspaceEdit, path: &str) -> Vec<String> {
+        let uri = Url::from_file_path(path).unwrap();
+        let edits = &edit.changes.as_ref().unwrap()[&uri];
+        edits.iter().map(|edit| edit.new_text.clone()).collect()
+    }
+
+    #[test]
+    fn rename_heading_and_link_fragments() {
+        let documents = open_documents([
+            ("/book/src/a.md", "# Old\n\n[Self](#old)\n"),
+            ("/book/src/b.md", "[A](a.md#old) [Other](a.md#other)\n"),
+        ]);
+        let edit = at_needle(
+            &documents,
+            "/book/src/a.md",
+            "Old",
+            |paths, path, source, offset| {
+                rename_heading(paths, path, source, offset, "New", &documents)
+            },
+        )
+        .unwrap();
+        assert_eq!(new_texts(&edit, "/book/src/a.md"), ["New", "new"]);
+        assert_eq!(new_texts(&edit, "/book/src/b.md"), ["new"]);
+    }
+
+    #[test]
+    fn rename_files_encoding_link_paths() {
+        let documents = open_documents([
+            ("/book/src/a.md", "[C](c.md) [S](sp%20ace.md) [Self](#x)\n"),
+            ("/book/src/c.md", "[A](a.md#x)\n\n{{#include a.md}}\n"),
+            ("/book/src/sp ace.md", "\n"),
+        ]);
+        let paths = test_book_paths();
+        let renames = HashMap::from([(
+            PathBuf::from("/book/src/a.md"),
+            PathBuf::from("/book/src/new dir/a b.md"),
+        )]);
+        let edit = rename_files(&paths, &renames, &documents);
+        assert_eq!(
+            new_texts(&edit, "/book/src/c.md"),
+            ["new%20dir/a%20b.md", "new dir/a b.md"]
+        );
+        assert_eq!(
+            new_texts(&edit, "/book/src/a.md"),
+            ["../c.md", "../sp%20ace.md"]
+        );
+        assert_eq!(edit.changes.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn rename_files_in_summary() {
+        let documents = open_documents([
+            (
+                "/book/src/SUMMARY.md",
+                "# Summary\n\n- [A](a.md)\n- [B](b%20\\(1\\).md)\n",
+            ),
+            ("/book/src/a.md", "# A\n"),
+            ("/book/src/b (1).md", "# B\n"),
+        ]);
+        let paths = test_book_paths();
+        let renames = HashMap::from([
+            (
+                PathBuf::from("/book/src/a.md"),
+                PathBuf::from("/book/src/new dir/a (2).md"),
+            ),
+            (
+                PathBuf::from("/book/src/b (1).md"),
+                PathBuf::from("/book/src/b.md"),
+            ),
+        ]);
+        let edit = rename_files(&paths, &renames, &documents);
+        let mut new_texts = new_texts(&edit, "/book/src/SUMMARY.md");
+        new_texts.sort();
+        assert_eq!(new_texts, [r"b.md", r"new%20dir/a%20\(2\).md"]);
+        assert_eq!(edit.changes.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,469 @@
+use super::*;
+
+/// State shared between [`MDBookLS`] and its background tasks.
+///
+/// NOTE: To not deadlock, lock `book_roots` before `build_errors`.
+#[derive(Debug, Default)]
+pub struct ServerState {
+    /// Absolute paths of the roots of the books in the workspace.
+    pub book_roots: RwLock<BTreeSet<PathBuf>>,
+    pub documents: RwLock<Documents>,
+    pub settings: RwLock<Settings>,
+    pub client_capabilities: RwLock<ClientCapabilities>,
+    /// Messages of failures building each book by its root,
+    /// by the absolute path of the file to blame.
+    pub build_errors: RwLock<HashMap<PathBuf, HashMap<PathBuf, String>>>,
+    /// URIs of the closed files we last published diagnostics for.
+    closed_file_uris: RwLock<HashSet<Url>>,
+    /// Structure of each book by its root, kept across keystrokes.
+    book_caches: RwLock<HashMap<PathBuf, Arc<BookCache>>>,
+    /// Markdown files of each book by its root, kept until files change.
+    source_files: RwLock<HashMap<PathBuf, Arc<SourceFiles>>>,
+}
+
+/// What diagnosing a book or finding its symbols needs besides its open documents,
+/// too costly to reload on each keystroke.
+#[derive(Debug)]
+struct BookCache {
+    maybe_book: Option<BookStructure>,
+    unrendered: Unrendered,
+    /// Symbols of each chapter and its headings by its absolute path,
+    /// loaded on the first workspace symbol query.
+    chapter_symbol_infos: RwLock<HashMap<PathBuf, Arc<[SymbolInformation]>>>,
+}
+
+impl BookCache {
+    /// Cached symbols of the chapter named `chapter_name` at `path`
+    /// and its headings, loading them if not cached.
+    fn chapter_symbol_infos(
+        &self,
+        chapter_name: &str,
+        path: &Path,
+        documents: &Documents,
+    ) -> Option<Arc<[SymbolInformation]>> {
+        if let Some(infos) = self.chapter_symbol_infos.read().unwrap().get(path) {
+            return Some(infos.clone());
+        }
+        let infos = chapter_symbol_infos(chapter_name, path, documents)?;
+        self.chapter_symbol_infos
+            .write()
+            .unwrap()
+            .insert(path.into(), infos.clone());
+        Some(infos)
+    }
+}
+
+/// Diagnostics of the open document at absolute `path` with `source`,
+/// in the book with `maybe_cache`, failing to build with `maybe_build_error`.
+fn document_diagnostics(
+    path: &Path,
+    source: &SourceText,
+    maybe_cache: Option<&BookCache>,
+    maybe_build_error: Option<&String>,
+    documents: &Documents,
+    settings: &DiagnosticSettings,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    if let Some(cache) = maybe_cache {
+        if let Some(book) = &cache.maybe_book {
+            if path.starts_with(&book.src_dir) {
+                diagnostics = diagnose_chapter(book, path, source, documents, settings);
+            }
+            if book.summary_md == path {
+                diagnostics.extend(diagnose_drafts(source, &cache.unrendered.drafts));
+            }
+        }
+        if cache
+            .unrendered
+            .orphans
+            .binary_search_by(|p| p.as_path().cmp(path))
+            .is_ok()
+        {
+            diagnostics.push(orphan_diagnostic());
+        }
+    }
+    if let Some(message) = maybe_build_error {
+        diagnostics.push(build_error_diagnostic(message));
+    }
+    diagnostics
+}
+
+impl ServerState {
+    /// If the client applies workspace edits that create files.
+    pub fn can_create_files(&self) -> bool {
+        let capabilities = self.client_capabilities.read().unwrap();
+        let Some(workspace_edit) = capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.workspace_edit.as_ref())
+        else {
+            return false;
+        };
+        workspace_edit.document_changes == Some(true)
+            && workspace_edit
+                .resource_operations
+                .as_ref()
+                .is_some_and(|operations| operations.contains(&ResourceOperationKind::Create))
+    }
+
+    /// Diagnose all open chapters and publish the diagnostics,
+    /// together with those of the build errors and unrendered chapters.
+    pub async fn publish_diagnostics(&self, client: &Client) {
+        let uri_diagnostics_n_versions = block_n_yield(|| self.diagnose()).await;
+        let stale_uris: Vec<_> = {
+            let closed_file_uris: HashSet<_> = uri_diagnostics_n_versions
+                .iter()
+                .filter(|(_, _, version)| version.is_none())
+                .map(|(uri, _, _)| uri.clone())
+                .collect();
+            let mut last_closed_file_uris = self.closed_file_uris.write().unwrap();
+            let stale_uris = last_closed_file_uris
+                .difference(&closed_file_uris)
+                .cloned()
+                .collect();
+            *last_closed_file_uris = closed_file_uris;
+            stale_uris
+        };
+        for uri in stale_uris {
+            client.publish_diagnostics(uri, vec![], None).await;
+        }
+        for (uri, diagnostics, version) in uri_diagnostics_n_versions {
+            client.publish_diagnostics(uri, diagnostics, version).await;
+        }
+    }
+
+    /// Absolute path of the root of the book containing `path`.
+    pub fn book_root(&self, path: &Path) -> Option<PathBuf> {
+        owning_book_root(&self.book_roots.read().unwrap(), path).cloned()
+    }
+
+    /// Paths of the files that define the structure of the book
+    /// containing `path`.
+    pub fn book_paths(&self, path: &Path) -> Result<BookPaths> {
+        let book_root = self
+            .book_root(path)
+            .with_context(|| format!("No book contains `{}`.", path.display()))?;
+        BookPaths::load(&book_root)
+    }
+
+    /// Forget the cached structures and files of all books,
+    /// e.g., because files were saved or changed on disk.
+    pub fn invalidate_books(&self) {
+        self.book_caches.write().unwrap().clear();
+        self.source_files.write().unwrap().clear();
+    }
+
+    /// Forget the cached structure and files of the book containing `path`,
+    /// e.g., because a file in it was saved, opened or closed.
+    pub fn invalidate_book(&self, path: &Path) {
+        if let Some(book_root) = self.book_root(path) {
+            self.book_caches.write().unwrap().remove(&book_root);
+            self.source_files.write().unwrap().remove(&book_root);
+        }
+    }
+
+    /// Forget the cached structure of the book containing `path` but
+    /// keep its files, e.g., because its `SUMMARY.md` was edited.
+    pub fn invalidate_summary(&self, path: &Path) {
+        if let Some(book_root) = self.book_root(path) {
+            self.book_caches.write().unwrap().remove(&book_root);
+        }
+    }
+
+    /// Forget the cached symbols and includes of the chapter at `path`,
+    /// e.g., because it changed without changing the book structure.
+    pub fn invalidate_chapter(&self, path: &Path) {
+        let Some(book_root) = self.book_root(path) else {
+            return;
+        };
+        if let Some(cache) = self.book_caches.read().unwrap().get(&book_root) {
+            cache.chapter_symbol_infos.write().unwrap().remove(path);
+        }
+        if let Some(files) = self.source_files.read().unwrap().get(&book_root) {
+            files.invalidate_includes(path);
+        }
+    }
+
+    /// If the open document at absolute `path` is the `SUMMARY.md` of
+    /// its book, so changing it changes the structure of the book.
+    pub fn is_summary_md(&self, path: &Path) -> bool {
+        let Some(book_root) = self.book_root(path) else {
+            return false;
+        };
+        let book_caches = self.book_caches.read().unwrap();
+        match book_caches
+            .get(&book_root)
+            .and_then(|cache| cache.maybe_book.as_ref())
+        {
+            Some(book) => book.summary_md == path,
+            None => BookPaths::load(&book_root).is_ok_and(|paths| paths.summary_md == path),
+        }
+    }
+
+    /// Chapters and headings in all books matching `query`,
+    /// from the cached book structures and chapter symbols.
+    pub fn workspace_symbols(&self, query: &str) -> Vec<SymbolInformation> {
+        let settings = self.settings.read().unwrap().diagnostics.clone();
+        let documents = self.documents.read().unwrap();
+        let book_roots = self.book_roots.read().unwrap();
+        let mut symbols = Vec::new();
+        for book_root in book_roots.iter() {
+            let cache = self.book_cache(book_root, &documents, &settings);
+            if let Some(book) = &cache.maybe_book {
+                symbols.extend(workspace_symbols(book, query, |name, path| {
+                    cache.chapter_symbol_infos(name, path, &documents)
+                }));
+            }
+        }
+        symbols
+    }
+
+    /// The cached structure of the book at `book_root`,
+    /// loading it if not cached.
+    fn book_cache(
+        &self,
+        book_root: &Path,
+        documents: &Documents,
+        settings: &DiagnosticSettings,
+    ) -> Arc<BookCache> {
+        if let Some(cache) = self.book_caches.read().unwrap().get(book_root) {
+            return cache.clone();
+        }
+        debug!(?book_root, "Loading the book structure.");
+        let maybe_book = BookStructure::load(book_root, documents)
+            .map_err(|err| warn!(?err, "Loading the book structure."))
+            .ok();
+        let unrendered = match &maybe_book {
+            Some(book) if settings.unrendered => {
+                let files = self.source_files(book_root, &book.src_dir, documents);
+                Unrendered::find(book, &files, documents)
+            }
+            _ => Unrendered::default(),
+        };
+        let cache = Arc::new(BookCache {
+            maybe_book,
+            unrendered,
+            chapter_symbol_infos: RwLock::default(),
+        });
+        self.book_caches
+            .write()
+            .unwrap()
+            .insert(book_root.into(), cache.clone());
+        cache
+    }
+
+    /// The cached Markdown files of the book at `book_root`
+    /// with source directory `src_dir`, loading them if not cached.
+    pub fn source_files(
+        &self,
+        book_root: &Path,
+        src_dir: &Path,
+        documents: &Documents,
+    ) -> Arc<SourceFiles> {
+        if let Some(files) = self.source_files.read().unwrap().get(book_root) {
+            return files.clone();
+        }
+        debug!(?book_root, "Loading the source files.");
+        let files = Arc::new(SourceFiles::load(src_dir, documents));
+        self.source_files
+            .write()
+            .unwrap()
+            .insert(book_root.into(), files.clone());
+        files
+    }
+
+    /// Diagnostics of the open document at absolute `path` alone,
+    /// with its version, if it is open.
+    fn diagnose_document(&self, path: &Path) -> Option<(Url, Vec<Diagnostic>, i32)> {
+        let settings = self.settings.read().unwrap().diagnostics.clone();
+        let documents = self.documents.read().unwrap();
+        let document = documents.get(path)?;
+        let maybe_cache = self
+            .book_root(path)
+            .map(|book_root| self.book_cache(&book_root, &documents, &settings));
+        let build_errors = self.build_errors.read().unwrap();
+        let maybe_build_error = build_errors
+            .values()
+            .find_map(|book_build_errors| book_build_errors.get(path))
+            .filter(|_| settings.build_errors);
+        let diagnostics = document_diagnostics(
+            path,
+            &document.source,
+            maybe_cache.as_deref(),
+            maybe_build_error,
+            &documents,
+            &settings,
+        );
+        Some((document.uri.clone(), diagnostics, document.version))
+    }
+
+    /// Re-check only the open document at absolute `path` and
+    /// publish its diagnostics, e.g., on each keystroke.
+    pub async fn publish_document_diagnostics(&self, client: &Client, path: &Path) {
+        if let Some((uri, diagnostics, version)) =
+            block_n_yield(|| self.diagnose_document(path)).await
+        {
+            client
+                .publish_diagnostics(uri, diagnostics, Some(version))
+                .await;
+        }
+    }
+
+    fn diagnose(&self) -> Vec<(Url, Vec<Diagnostic>, Option<i32>)> {
+        let settings = self.settings.read().unwrap().diagnostics.clone();
+        let documents = self.documents.read().unwrap();
+        let book_roots = self.book_roots.read().unwrap();
+        let no_build_errors = HashMap::new();
+        let build_errors = self.build_errors.read().unwrap();
+        let build_errors = match settings.build_errors {
+            true => &*build_errors,
+            false => &no_build_errors,
+        };
+        let books: HashMap<_, _> = book_roots
+            .iter()
+            .map(|book_root| (book_root, self.book_cache(book_root, &documents, &settings)))
+            .collect();
+        let orphans: HashSet<_> = books
+            .values()
+            .flat_map(|cache| &cache.unrendered.orphans)
+            .collect();
+        let build_error = |path: &Path| {
+            build_errors
+                .values()
+                .find_map(|book_build_errors| book_build_errors.get(path))
+        };
+
+        let open_documents = documents.iter().map(|(path, document)| {
+            let owning_book = owning_book_root(&book_roots, path).and_then(|root| books.get(root));
+            let diagnostics = document_diagnostics(
+                path,
+                &document.source,
+                owning_book.map(Arc::as_ref),
+                build_error(path),
+                &documents,
+                &settings,
+            );
+            (document.uri.clone(), diagnostics, Some(document.version))
+        });
+
+        let mut closed_files: HashMap<&Path, Vec<Diagnostic>> = HashMap::new();
+        for (path, message) in build_errors.values().flatten() {
+            let diagnostic = build_error_diagnostic(message);
+            closed_files.entry(path).or_default().push(diagnostic);
+        }
+        for path in &orphans {
+            closed_files
+                .entry(path)
+                .or_default()
+                .push(orphan_diagnostic());
+        }
+        for cache in books.values() {
+            let Some(book) = cache
+                .maybe_book
+                .as_ref()
+                .filter(|_| !cache.unrendered.drafts.is_empty())
+            else {
+                continue;
+            };
+            if let Some(summary) = read_source(&documents, &book.summary_md) {
+                let drafts = diagnose_drafts(&summary, &cache.unrendered.drafts);
+                closed_files
+                    .entry(&book.summary_md)
+                    .or_default()
+                    .extend(drafts);
+            }
+        }
+        let closed_files = closed_files
+            .into_iter()
+            .filter(|(path, _)| !documents.contains_key(*path))
+            .filter_map(|(path, diagnostics)| {
+                let uri = Url::from_file_path(path).ok()?;
+                Some((uri, diagnostics, None))
+            });
+        open_documents.chain(closed_files).collect()
+    }
+
+    /// Update the build errors of the book at `book_root` according to
+    /// `report`.
+    /// Return if they changed.
+    fn update_build_errors(&self, book_root: &Path, report: BuildReport) -> bool {
+        let mut all_build_errors = self.build_errors.write().unwrap();
+        let build_errors = all_build_errors.entry(book_root.into()).or_default();
+        match report {
+            BuildReport::Rebuilt => {
+                let changed = !build_errors.is_empty();
+                build_errors.clear();
+                changed
+            }
+            BuildReport::Patched(path) => build_errors.remove(&path).is_some(),
+            BuildReport::Failed { path, message } => {
+                build_errors.insert(path, message.clone()) != Some(message)
+            }
+            BuildReport::ShowSource { .. } => false,
+        }
+    }
+}
+
+/// Publish diagnostics for the [`BuildReport`]s received
+/// from the previewer of the book at `book_root`,
+/// and show the sources the browser tabs ask for.
+pub async fn handle_build_reports(
+    client: Client,
+    state: Arc<ServerState>,
+    book_root: PathBuf,
+    mut build_report_rx: mpsc::Receiver<BuildReport>,
+) {
+    while let Some(report) = build_report_rx.recv().await {
+        debug!(?book_root, ?report, "Received build report.");
+        if let BuildReport::ShowSource { path, line } = report {
+            // NOTE: Do not hold up the reports while the client responds.
+            spawn(show_source(client.clone(), path, line));
+            continue;
+        }
+        // NOTE: The previewer rebuilds when the book's files change on disk.
+        let rebuilt = matches!(report, BuildReport::Rebuilt);
+        if rebuilt {
+            state.invalidate_book(&book_root);
+        }
+        if state.update_build_errors(&book_root, report) || rebuilt {
+            state.publish_diagnostics(&client).await;
+        }
+    }
+}
+
+/// Ask the client to show zero-based `line` of the file at absolute `path`.
+async fn show_source(client: Client, path: PathBuf, line: usize) {
+    let Ok(uri) = Url::from_file_path(&path) else {
+        return error!(?path, "Converting the path to a URI.");
+    };
+    let position = Position::new(line as u32, 0);
+    let params = ShowDocumentParams {
+        uri,
+        external: None,
+        take_focus: Some(true),
+        selection: Some(lsp_types::Range::new(position, position)),
+    };
+    match client.show_document(params).await {
+        Ok(true) => {}
+        Ok(false) => warn!(?path, line, "The client did not show the source."),
+        Err(err) => warn!(?err, ?path, line, "Asking the client to show the source."),
+    }
+}
+
+/// Diagnostic for the build error `message`,
+/// on the line it mentions if any.
+fn build_error_diagnostic(message: &str) -> Diagnostic {
+    static LINE_COLUMN: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"line (\d+),? column (\d+)").unwrap());
+    let line = LINE_COLUMN
+        .captures(message)
+        .and_then(|cap| cap[1].parse::<u32>().ok())
+        .map_or(0, |line| line.saturating_sub(1));
+    Diagnostic {
+        range: lsp_types::Range::new(Position::new(line, 0), Position::new(line + 1, 0)),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some(DIAGNOSTIC_SOURCE.into()),
+        message: message.into(),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,93 @@
+use super::*;
+
+/// Settings from `initializationOptions` and
+/// `workspace/didChangeConfiguration`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    /// Address to serve the preview at
+    /// unless `open_preview` specifies one.
+    pub preview_address: SocketAddr,
+    /// If to open the browser when starting the preview.
+    pub open_browser: bool,
+    /// If to start the preview when opening a chapter while
+    /// there is no preview.
+    pub auto_start_preview: bool,
+    /// Milliseconds to wait for file changes to settle before rebuilding.
+    pub debounce_ms: u64,
+    /// If to scroll the preview to the first changed block after each patch.
+    pub scroll_to_changes: bool,
+    pub diagnostics: DiagnosticSettings,
+    /// Log filter in the `RUST_LOG` syntax, e.g., `mdbook_ls=debug`.
+    pub log_level: Option<String>,
+}
+
+impl Settings {
+    /// Parse the settings from `value`,
+    /// either the settings themselves or nested under `mdbook_ls`.
+    pub fn parse(value: Value) -> Result<Self> {
+        let value = match value {
+            Value::Null => return Ok(Self::default()),
+            Value::Object(mut map) if map.contains_key(SETTINGS_SECTION) => {
+                map.remove(SETTINGS_SECTION).unwrap()
+            }
+            value => value,
+        };
+        serde_json::from_value(value).context("Parsing the settings")
+    }
+
+    pub fn preview_settings(&self) -> PreviewSettings {
+        PreviewSettings {
+            socket_address: self.preview_address,
+            open_browser: self.open_browser,
+            debounce: Duration::from_millis(self.debounce_ms),
+            scroll_to_changes: self.scroll_to_changes,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let preview_settings = PreviewSettings::default();
+        Self {
+            preview_address: preview_settings.socket_address,
+            open_browser: preview_settings.open_browser,
+            auto_start_preview: false,
+            debounce_ms: preview_settings.debounce.as_millis() as u64,
+            scroll_to_changes: preview_settings.scroll_to_changes,
+            diagnostics: Default::default(),
+            log_level: None,
+        }
+    }
+}
+
+/// Which kinds of diagnostics to publish.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DiagnosticSettings {
+    /// Broken links to chapters and heading anchors.
+    pub links: bool,
+    /// Bad paths, anchors and line ranges in `{{#include}}`s.
+    pub includes: bool,
+    /// Files `SUMMARY.md` does not list and draft chapters.
+    pub unrendered: bool,
+    /// Failures building the book.
+    pub build_errors: bool,
+}
+
+impl Default for DiagnosticSettings {
+    fn default() -> Self {
+        Self {
+            links: true,
+            includes: true,
+            unrendered: true,
+            build_errors: true,
+        }
+    }
+}
+
+/// Key of the settings in the client's configuration.
+const SETTINGS_SECTION: &str = "mdbook_ls";
+
+/// Sets the log filter from the `RUST_LOG`-syntax directives.
+pub type SetLogFilter = Box<dyn Fn(&str) -> Result<()> + Send + Sync>;
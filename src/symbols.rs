@@ -0,0 +1,350 @@
+use super::*;
+
+/// Outline of the chapter at absolute `path` with `source`:
+/// its headings nested by level, including those its `{{#include}}`s
+/// bring in, detailed with the anchor IDs mdBook assigns.
+pub fn chapter_symbols(
+    path: &Path,
+    source: &SourceText,
+    documents: &Documents,
+) -> Vec<DocumentSymbol> {
+    let items = chapter_headings(path, &source.text, documents)
+        .into_iter()
+        .map(|heading| {
+            let detail = format!("#{}", heading.id);
+            let symbol = make_symbol(
+                source,
+                heading.title,
+                Some(detail),
+                SymbolKind::STRING,
+                &heading.range,
+            );
+            (heading.level, symbol)
+        });
+    nest_symbols(source, items)
+}
+
+/// Outline of `SUMMARY.md`: part titles containing their numbered chapters,
+/// and the prefix and suffix chapters around them.
+pub fn summary_symbols(source: &SourceText) -> Vec<DocumentSymbol> {
+    let items = parse_summary_entries(&source.text)
+        .into_iter()
+        .map(|entry| {
+            let (kind, detail) = match &entry.kind {
+                SummaryEntryKind::PartTitle => (SymbolKind::NAMESPACE, None),
+                _ if entry.is_draft() => (SymbolKind::FILE, Some("draft".into())),
+                SummaryEntryKind::NumberedChapter(number) => {
+                    let number: String = number.iter().map(|n| format!("{n}.")).collect();
+                    (
+                        SymbolKind::FILE,
+                        Some(format!("{number} {}", entry.location)),
+                    )
+                }
+                _ => (SymbolKind::FILE, Some(entry.location.clone())),
+            };
+            let symbol = make_symbol(source, entry.name, detail, kind, &entry.range);
+            (entry.level, symbol)
+        });
+    nest_symbols(source, items)
+}
+
+/// Chapters listed in `SUMMARY.md` and the headings in them
+/// whose names contain all the words in `query`, case-insensitively,
+/// given the symbols of each chapter by `chapter_symbol_infos`.
+pub fn workspace_symbols(
+    book: &BookStructure,
+    query: &str,
+    mut chapter_symbol_infos: impl FnMut(&str, &Path) -> Option<Arc<[SymbolInformation]>>,
+) -> Vec<SymbolInformation> {
+    let query = query.to_lowercase();
+    let matches = |name: &str| {
+        let name = name.to_lowercase();
+        query.split_whitespace().all(|word| name.contains(word))
+    };
+    let mut symbols = Vec::new();
+    for (chapter_name, path) in &book.chapters {
+        if let Some(infos) = chapter_symbol_infos(chapter_name, path) {
+            let matching = infos.iter().filter(|info| matches(&info.name));
+            symbols.extend(matching.cloned());
+        }
+    }
+    symbols
+}
+
+/// Symbol of the chapter named `chapter_name` at `path`,
+/// followed by those of the headings in it.
+pub fn chapter_symbol_infos(
+    chapter_name: &str,
+    path: &Path,
+    documents: &Documents,
+) -> Option<Arc<[SymbolInformation]>> {
+    let (Ok(uri), Some(source)) = (Url::from_file_path(path), read_source(documents, path)) else {
+        return None;
+    };
+    let chapter = make_symbol_info(&source, chapter_name, None, &uri, &(0..0));
+    let headings = parse_chapter(&source.text)
+        .headings
+        .into_iter()
+        .map(|heading| {
+            make_symbol_info(
+                &source,
+                &heading.title,
+                Some(chapter_name),
+                &uri,
+                &heading.range,
+            )
+        });
+    Some(iter::once(chapter).chain(headings).collect())
+}
+
+/// Nest the `(level, symbol)`s under the closest preceding symbol of a lower
+/// level, extending each symbol's range until the next one of the same or
+/// a lower level.
+fn nest_symbols(
+    source: &SourceText,
+    items: impl IntoIterator<Item = (usize, DocumentSymbol)>,
+) -> Vec<DocumentSymbol> {
+    let mut roots = Vec::new();
+    let mut stack: Vec<(usize, DocumentSymbol)> = Vec::new();
+    for (level, symbol) in items {
+        while stack
+            .last()
+            .is_some_and(|(open_level, _)| *open_level >= level)
+        {
+            let (_, finished) = stack.pop().unwrap();
+            close_symbol(finished, symbol.range.start, &mut stack, &mut roots);
+        }
+        stack.push((level, symbol));
+    }
+    let end = source.position(source.text.len());
+    while let Some((_, finished)) = stack.pop() {
+        close_symbol(finished, end, &mut stack, &mut roots);
+    }
+    roots
+}
+
+fn close_symbol(
+    mut symbol: DocumentSymbol,
+    end: Position,
+    stack: &mut [(usize, DocumentSymbol)],
+    roots: &mut Vec<DocumentSymbol>,
+) {
+    // NOTE: Headings from one `{{#include}}` share its range, and
+    // LSP requires the selection range to be in the range.
+    symbol.range.end = end.max(symbol.selection_range.end);
+    match stack.last_mut() {
+        Some((_, parent)) => parent.children.get_or_insert_with(Vec::new).push(symbol),
+        None => roots.push(symbol),
+    }
+}
+
+fn make_symbol(
+    source: &SourceText,
+    name: String,
+    detail: Option<String>,
+    kind: SymbolKind,
+    range: &Range<usize>,
+) -> DocumentSymbol {
+    let range = source.range(range);
+    // NOTE: Clients reject symbols with empty names.
+    let name = match name.trim() {
+        "" => "(untitled)".into(),
+        _ => name,
+    };
+    #[allow(deprecated)]
+    DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range: range,
+        children: None,
+    }
+}
+
+/// Symbol of a chapter if without `container_name`, otherwise of a heading.
+fn make_symbol_info(
+    source: &SourceText,
+    name: &str,
+    container_name: Option<&str>,
+    uri: &Url,
+    range: &Range<usize>,
+) -> SymbolInformation {
+    #[allow(deprecated)]
+    SymbolInformation {
+        name: name.into(),
+        kind: match container_name {
+            Some(_) => SymbolKind::STRING,
+            None => SymbolKind::FILE,
+        },
+        tags: None,
+        deprecated: None,
+        location: Location::new(uri.clone(), source.range(range)),
+        container_name: container_name.map(Into::into),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Names, details, and line ranges of the symbols, depth-first.
+    fn outline(symbols: &[DocumentSymbol], depth: usize, out: &mut Vec<String>) {
+        for symbol in symbols {
+            let (start, end) = (symbol.range.start.line, symbol.range.end.line);
+            let detail = symbol.detail.as_deref().unwrap_or_default();
+            out.push(format!(
+                "{}{} {detail} {start}-{end}",
+                "  ".repeat(depth),
+                symbol.name
+            ));
+            outline(
+                symbol.children.as_deref().unwrap_or_default(),
+                depth + 1,
+                out,
+            );
+        }
+    }
+
+    #[test]
+    fn nest_chapter_headings() {
+        let source = SourceText::new(
+            "# A
+## B
+### C
+## B
+# D
+### E
+"
+            .into(),
+        );
+        let mut out = vec![];
+        let path = Path::new("/book/src/chapter.md");
+        outline(
+            &chapter_symbols(path, &source, &Documents::new()),
+            0,
+            &mut out,
+        );
+        assert_eq!(
+            out,
+            [
+                "A #a 0-4",
+                "  B #b 1-3",
+                "    C #c 2-3",
+                "  B #b-1 3-4",
+                "D #d 4-6",
+                "  E #e 5-6",
+            ]
+        );
+    }
+
+    #[test]
+    fn number_chapter_headings_after_includes() {
+        let text = "# A\n{{#include part.md}}\n## A\n";
+        let documents = open_documents([("/book/src/part.md", "## A\n")]);
+        let path = Path::new("/book/src/chapter.md");
+        let mut out = vec![];
+        outline(
+            &chapter_symbols(path, &SourceText::new(text.into()), &documents),
+            0,
+            &mut out,
+        );
+        assert_eq!(out, ["A #a 0-3", "  A #a-1 1-2", "  A #a-2 2-3"]);
+    }
+
+    #[test]
+    fn contain_selection_ranges_of_included_headings() {
+        let text = "# A\n{{#include part.md}}\n";
+        let documents = open_documents([("/book/src/part.md", "## B\n## C\n")]);
+        let path = Path::new("/book/src/chapter.md");
+        let symbols = chapter_symbols(path, &SourceText::new(text.into()), &documents);
+        let children = symbols[0].children.as_deref().unwrap_or_default();
+        let names: Vec<_> = children.iter().map(|symbol| symbol.name.as_str()).collect();
+        assert_eq!(names, ["B", "C"]);
+        for symbol in children {
+            let (range, selection) = (symbol.range, symbol.selection_range);
+            assert!(range.start <= selection.start && selection.end <= range.end);
+            assert!(range.end <= symbols[0].range.end);
+        }
+    }
+
+    #[test]
+    fn nest_summary_entries() {
+        let source = SourceText::new(
+            "# Summary\n\n[Intro](intro.md)\n\n# Part\n\n- [One](one.md)\n    - [Draft]()\n- [Two](two.md)\n\n[Outro](outro.md)\n"
+                .into(),
+        );
+        let mut out = vec![];
+        outline(&summary_symbols(&source), 0, &mut out);
+        assert_eq!(
+            out,
+            [
+                "Intro intro.md 2-4",
+                "Part  4-10",
+                "  One 1. one.md 6-8",
+                "    Draft draft 7-8",
+                "  Two 2. two.md 8-10",
+                "Outro outro.md 10-11",
+            ]
+        );
+    }
+
+    #[test]
+    fn nest_summary_entries_without_part_titles() {
+        let source = SourceText::new(
+            "# Summary\n\n[Intro](intro.md)\n\n- [One](one.md)\n    - [Sub](sub.md)\n- [Two](two.md)\n\n[Outro](outro.md)\n"
+                .into(),
+        );
+        let mut out = vec![];
+        outline(&summary_symbols(&source), 0, &mut out);
+        assert_eq!(
+            out,
+            [
+                "Intro intro.md 2-4",
+                "One 1. one.md 4-6",
+                "  Sub 1.1. sub.md 5-6",
+                "Two 2. two.md 6-8",
+                "Outro outro.md 8-9",
+            ]
+        );
+    }
+
+    #[test]
+    fn search_workspace_symbols() {
+        let documents = open_documents([
+            ("/book/src/one.md", "# Getting started\n## Install\n"),
+            ("/book/src/two.md", "# Start over\n"),
+        ]);
+        let book = BookStructure {
+            book_root: "/book".into(),
+            src_dir: "/book/src".into(),
+            summary_md: "/book/src/SUMMARY.md".into(),
+            chapter_paths: HashSet::new(),
+            chapters: vec![
+                ("One".into(), "/book/src/one.md".into()),
+                ("Two start".into(), "/book/src/two.md".into()),
+            ],
+            drafts: vec![],
+        };
+        let mut n_loads = 0;
+        let symbols = workspace_symbols(&book, "START", |name, path| {
+            n_loads += 1;
+            chapter_symbol_infos(name, path, &documents)
+        });
+        let names: Vec<_> = symbols
+            .iter()
+            .map(|info| (info.name.as_str(), info.container_name.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("Getting started", Some("One")),
+                ("Two start", None),
+                ("Start over", Some("Two start")),
+            ]
+        );
+        assert_eq!(n_loads, 2);
+    }
+}
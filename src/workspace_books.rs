@@ -0,0 +1,98 @@
+use super::*;
+
+/// The [`Previewer`] of a book in the workspace,
+/// and the task publishing its build errors.
+#[derive(Debug)]
+pub struct BookPreviewer {
+    handle: ActorHandle<ActorMsg<Previewer>>,
+    pub previewer: ActorRef<Previewer>,
+    build_report_handle: JoinHandle<()>,
+}
+
+impl BookPreviewer {
+    /// Spawn a previewer of the book at absolute `book_root`.
+    pub async fn spawn(
+        client: Client,
+        state: Arc<ServerState>,
+        book_root: PathBuf,
+    ) -> Result<Self> {
+        let (build_report_tx, build_report_rx) = mpsc::channel(8);
+        let previewer = Previewer::try_new(Some(build_report_tx))?;
+        let (tx, msg_receiver) = mpsc::channel(8);
+        let (handle, previewer) = previewer.spawn_with_channel(tx, msg_receiver);
+        previewer
+            .cast(PreviewInfo::BookRoot(book_root.clone()))
+            .await?;
+        let preview_settings = state.settings.read().unwrap().preview_settings();
+        previewer
+            .cast(PreviewInfo::Settings(preview_settings))
+            .await?;
+        let build_report_handle = spawn(handle_build_reports(
+            client,
+            state,
+            book_root,
+            build_report_rx,
+        ));
+        Ok(Self {
+            handle,
+            previewer,
+            build_report_handle,
+        })
+    }
+
+    /// Stop the previewer and wait for it to exit.
+    pub async fn shut_down(self) {
+        self.build_report_handle.abort();
+        self.previewer.cancel();
+        match self.handle.await {
+            Ok((_, Err(err))) => error!(?err, "Previewer exited."),
+            Err(err) => error!(?err, "Joining the previewer."),
+            Ok(_) => {}
+        }
+    }
+
+    pub fn abort(&self) {
+        self.handle.abort();
+        self.build_report_handle.abort();
+    }
+}
+
+/// Absolute paths of the directories containing a `book.toml` in `folder`,
+/// skipping hidden and Git-ignored directories.
+/// If there is none, the book containing `folder`, if any.
+pub fn find_book_roots(folder: &Path) -> BTreeSet<PathBuf> {
+    let mut book_roots: BTreeSet<PathBuf> = WalkBuilder::new(folder)
+        .build()
+        .filter_map(|entry| {
+            entry
+                .map_err(|err| warn!(?err, ?folder, "Walking the workspace folder."))
+                .ok()
+        })
+        .filter(|entry| {
+            entry.file_name() == "book.toml" && entry.file_type().is_some_and(|t| t.is_file())
+        })
+        .filter_map(|entry| entry.path().parent().map(Into::into))
+        .collect();
+    if book_roots.is_empty() {
+        match find_book_root(folder) {
+            Some(book_root) => _ = book_roots.insert(book_root),
+            None => info!(?folder, "No `book.toml` in or above the workspace folder."),
+        }
+    }
+    book_roots
+}
+
+/// The closest directory at or above `path` containing a `book.toml`.
+pub fn find_book_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|dir| dir.join("book.toml").is_file())
+        .map(Into::into)
+}
+
+/// The innermost of the `book_roots` containing `path`.
+pub fn owning_book_root<'a>(book_roots: &'a BTreeSet<PathBuf>, path: &Path) -> Option<&'a PathBuf> {
+    book_roots
+        .iter()
+        .rev()
+        .find(|book_root| path.starts_with(book_root))
+}